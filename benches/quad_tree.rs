@@ -0,0 +1,62 @@
+//! Insert and lookup timings for `InfiniteQuadTree` at increasing sizes.
+//!
+//! ```text
+//! cargo bench --bench quad_tree
+//! ```
+//!
+//! Per-item times should grow roughly logarithmically with the number of items.
+
+use std::{hint::black_box, time::{Duration, Instant}};
+use electron_architect_rs_2::{graph::quad_tree::InfiniteQuadTree, vector2i::Vector2i};
+use rand::prelude::*;
+
+const SIZES: [usize; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+/// Row-major positions on a square grid, the order circuits tend to be built in
+fn grid_positions(count: usize) -> Vec<Vector2i> {
+    let side = (count as f64).sqrt().ceil() as usize;
+    (0..count)
+        .map(|i| Vector2i::new((i % side) as i32, (i / side) as i32))
+        .collect()
+}
+
+/// The same positions, shuffled
+fn random_positions(count: usize) -> Vec<Vector2i> {
+    let mut positions = grid_positions(count);
+    positions.shuffle(&mut StdRng::seed_from_u64(0));
+    positions
+}
+
+fn per_item(elapsed: Duration, count: usize) -> f64 {
+    elapsed.as_nanos() as f64 / count as f64
+}
+
+fn bench(name: &str, positions: &[Vector2i]) {
+    let start = Instant::now();
+    let mut tree = InfiniteQuadTree::new();
+    for &position in positions {
+        tree.insert(position);
+    }
+    let insert = start.elapsed();
+
+    let mut lookup_order = positions.to_vec();
+    lookup_order.shuffle(&mut StdRng::seed_from_u64(1));
+    let start = Instant::now();
+    for &position in lookup_order.iter() {
+        black_box(tree.at(black_box(position)));
+    }
+    let lookup = start.elapsed();
+
+    println!(
+        "{name:>7} {:>9} {:>12.1} {:>12.1}",
+        positions.len(), per_item(insert, positions.len()), per_item(lookup, positions.len()),
+    );
+}
+
+fn main() {
+    println!("{:>7} {:>9} {:>12} {:>12}", "order", "items", "insert ns", "lookup ns");
+    for size in SIZES {
+        bench("grid", &grid_positions(size));
+        bench("random", &random_positions(size));
+    }
+}
@@ -0,0 +1,336 @@
+//! Runs a saved circuit without opening a window.
+//!
+//! ```text
+//! simulate <circuit> [--ticks N] [--stimulus FILE] [--set LABEL=0|1]... [--expect LABEL=0|1]... [--json]
+//! ```
+//!
+//! Inputs are labeled `always`, `never`, `toggle` or `button` nodes, which `set` drives high or low. A stimulus file holds one event per line:
+//!
+//! ```text
+//! # <tick> set <label> <0|1>
+//! 0 set a 1
+//! # <tick> expect <label> <0|1>, checked after the tick has run
+//! 3 expect q 1
+//! ```
+//!
+//! `--set` applies before the first tick and `--expect` is checked after the last. Events past the last tick are an error.
+//! Prints the state of every labeled node after each tick, with buses in hexadecimal and as plain numbers in JSON.
+//! Expectations read a bus as high if any of its bits are. Exits with 1 if any expectation failed and 2 on bad input.
+
+use std::{fs::{self, File}, io::BufReader, process::ExitCode};
+use electron_architect_rs_2::graph::{signal::Signal, Graph};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Set(bool),
+    Expect(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event {
+    tick: u64,
+    label: String,
+    action: Action,
+}
+
+#[derive(Debug)]
+struct Failure {
+    tick: u64,
+    label: String,
+    expected: bool,
+    actual: bool,
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    circuit: String,
+    ticks: Option<u64>,
+    stimulus: Option<String>,
+    /// From `--set`, applied on tick 0
+    sets: Vec<(String, bool)>,
+    /// From `--expect`, checked on the last tick
+    expects: Vec<(String, bool)>,
+    json: bool,
+}
+
+const USAGE: &str = "usage: simulate <circuit> [--ticks N] [--stimulus FILE] [--set LABEL=0|1]... [--expect LABEL=0|1]... [--json]";
+
+fn parse_bit(text: &str) -> Result<bool, String> {
+    match text {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(format!("expected 0 or 1, got `{text}`")),
+    }
+}
+
+fn parse_assignment(text: &str) -> Result<(String, bool), String> {
+    let (label, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected LABEL=0|1, got `{text}`"))?;
+    Ok((label.to_string(), parse_bit(value)?))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut circuit = None;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{name} needs a value"));
+        match arg.as_str() {
+            "--ticks" => {
+                let ticks = value("--ticks")?;
+                options.ticks = Some(ticks.parse().map_err(|_| format!("invalid tick count `{ticks}`"))?);
+            },
+            "--stimulus" => options.stimulus = Some(value("--stimulus")?),
+            "--set" => options.sets.push(parse_assignment(&value("--set")?)?),
+            "--expect" => options.expects.push(parse_assignment(&value("--expect")?)?),
+            "--json" => options.json = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{arg}`")),
+            _ if circuit.is_none() => circuit = Some(arg),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+    options.circuit = circuit.ok_or_else(|| String::from("missing circuit file"))?;
+    Ok(options)
+}
+
+fn parse_stimulus(text: &str) -> Result<Vec<Event>, String> {
+    let mut events = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line_error = |message: String| format!("line {}: {message}", index + 1);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let [tick, kind, label, value] = tokens[..] else {
+            return Err(line_error(String::from("expected `<tick> set|expect <label> <0|1>`")));
+        };
+        let tick = tick.parse().map_err(|_| line_error(format!("invalid tick `{tick}`")))?;
+        let value = parse_bit(value).map_err(line_error)?;
+        let action = match kind {
+            "set" => Action::Set(value),
+            "expect" => Action::Expect(value),
+            _ => return Err(line_error(format!("unknown event `{kind}`"))),
+        };
+        events.push(Event { tick, label: label.to_string(), action });
+    }
+    Ok(events)
+}
+
+/// Drives the input labeled `label` high or low.
+fn drive(graph: &mut Graph, label: &str, value: bool) -> Result<(), String> {
+    let id = graph
+        .find_node_by_label(label)
+        .ok_or_else(|| format!("no node labeled `{label}`"))?;
+    if !graph.drive(id, value) {
+        let gate = graph.node(id).map(|node| graph.gate_name(node.gate())).unwrap_or_default();
+        return Err(format!("`{label}` is a {gate} gate, which can't be driven"));
+    }
+    Ok(())
+}
+
+/// Rejects events for labels the circuit doesn't have or for ticks that won't be run, rather than quietly dropping them.
+fn check_events(graph: &Graph, events: &[Event], ticks: u64) -> Result<(), String> {
+    for event in events.iter() {
+        if graph.find_node_by_label(&event.label).is_none() {
+            return Err(format!("no node labeled `{}`", event.label));
+        }
+        if event.tick >= ticks {
+            return Err(format!("event for `{}` on tick {} is past the last tick, {}", event.label, event.tick, ticks - 1));
+        }
+    }
+    Ok(())
+}
+
+fn bit(value: bool) -> u8 {
+    value as u8
+}
+
+fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn run(options: Options) -> Result<Vec<Failure>, String> {
+    let file = File::open(&options.circuit).map_err(|e| format!("{}: {e}", options.circuit))?;
+    let mut graph = Graph::load(BufReader::new(file)).map_err(|e| format!("{}: {e}", options.circuit))?;
+
+    let mut events = match &options.stimulus {
+        Some(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+            parse_stimulus(&text).map_err(|e| format!("{path}: {e}"))?
+        },
+        None => Vec::new(),
+    };
+    let ticks = options.ticks
+        .unwrap_or_else(|| events.iter().map(|event| event.tick + 1).max().unwrap_or(1))
+        .max(1);
+    events.extend(options.sets.into_iter().map(|(label, value)| Event { tick: 0, label, action: Action::Set(value) }));
+    events.extend(options.expects.into_iter().map(|(label, value)| Event { tick: ticks - 1, label, action: Action::Expect(value) }));
+    events.sort_by_key(|event| event.tick);
+
+    check_events(&graph, &events, ticks)?;
+
+    let labeled = graph.labeled_nodes();
+    let labels: Vec<String> = labeled
+        .iter()
+        .filter_map(|&id| graph.node(id))
+        .map(|node| node.label().unwrap_or_default().to_string())
+        .collect();
+    let mut rows: Vec<(u64, Vec<Signal>)> = Vec::with_capacity(ticks as usize);
+    let mut failures = Vec::new();
+    let mut pending = events.iter().peekable();
+    for tick in 0..ticks {
+        let mut expects = Vec::new();
+        while let Some(event) = pending.next_if(|event| event.tick == tick) {
+            match event.action {
+                Action::Set(value) => drive(&mut graph, &event.label, value)?,
+                Action::Expect(expected) => expects.push((&event.label, expected)),
+            }
+        }
+
+        graph.step();
+
+        for (label, expected) in expects {
+            let actual = graph
+                .find_node_by_label(label)
+                .and_then(|id| graph.node(id))
+                .is_some_and(|node| node.state());
+            if actual != expected {
+                failures.push(Failure { tick, label: label.clone(), expected, actual });
+            }
+        }
+        rows.push((tick, labeled.iter().map(|&id| graph.node(id).map_or(Signal::LOW, |node| node.value())).collect()));
+    }
+
+    if options.json {
+        println!("{{");
+        println!("  \"ticks\": [");
+        for (i, (tick, values)) in rows.iter().enumerate() {
+            let values: Vec<String> = labels
+                .iter()
+                .zip(values)
+                .map(|(label, value)| format!("{}: {}", json_string(label), value.bits()))
+                .collect();
+            let comma = if i + 1 < rows.len() { "," } else { "" };
+            println!("    {{\"tick\": {tick}, \"values\": {{{}}}}}{comma}", values.join(", "));
+        }
+        println!("  ],");
+        println!("  \"failures\": [");
+        for (i, failure) in failures.iter().enumerate() {
+            let comma = if i + 1 < failures.len() { "," } else { "" };
+            println!(
+                "    {{\"tick\": {}, \"label\": {}, \"expected\": {}, \"actual\": {}}}{comma}",
+                failure.tick, json_string(&failure.label), bit(failure.expected), bit(failure.actual),
+            );
+        }
+        println!("  ]");
+        println!("}}");
+    } else {
+        let tick_width = "tick".len().max((ticks - 1).to_string().len());
+        let mut header = format!("{:>tick_width$}", "tick");
+        for label in labels.iter() {
+            header.push_str(&format!(" {label}"));
+        }
+        println!("{header}");
+        for (tick, values) in rows.iter() {
+            let mut row = format!("{tick:>tick_width$}");
+            for (label, value) in labels.iter().zip(values) {
+                row.push_str(&format!(" {:>width$}", value.to_string(), width = label.len()));
+            }
+            println!("{row}");
+        }
+        for failure in failures.iter() {
+            eprintln!(
+                "tick {}: expected {} to be {}, got {}",
+                failure.tick, failure.label, bit(failure.expected), bit(failure.actual),
+            );
+        }
+    }
+
+    Ok(failures)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return ExitCode::from(2);
+        },
+    };
+    match run(options) {
+        Ok(failures) if failures.is_empty() => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> impl Iterator<Item = String> + '_ {
+        text.split_whitespace().map(String::from)
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(args("adder.txt --ticks 8 --set a=1 --set b=0 --expect sum=1 --json")).unwrap();
+        assert_eq!(options.circuit, "adder.txt");
+        assert_eq!(options.ticks, Some(8));
+        assert_eq!(options.sets, [(String::from("a"), true), (String::from("b"), false)]);
+        assert_eq!(options.expects, [(String::from("sum"), true)]);
+        assert!(options.json);
+
+        assert!(parse_args(args("--ticks 8")).is_err(), "circuit is required");
+        assert!(parse_args(args("a.txt --ticks")).is_err());
+        assert!(parse_args(args("a.txt --ticks many")).is_err());
+        assert!(parse_args(args("a.txt --set a=2")).is_err());
+        assert!(parse_args(args("a.txt --set a")).is_err());
+        assert!(parse_args(args("a.txt b.txt")).is_err());
+        assert!(parse_args(args("a.txt --verbose")).is_err());
+    }
+
+    #[test]
+    fn test_parse_stimulus() {
+        let events = parse_stimulus("# comment\n\n0 set a 1\n  3 expect q 0\n").unwrap();
+        assert_eq!(events, [
+            Event { tick: 0, label: String::from("a"), action: Action::Set(true) },
+            Event { tick: 3, label: String::from("q"), action: Action::Expect(false) },
+        ]);
+
+        assert!(parse_stimulus("0 set a").unwrap_err().starts_with("line 1:"));
+        assert!(parse_stimulus("\n0 toggle a 1").unwrap_err().starts_with("line 2:"));
+        assert!(parse_stimulus("x set a 1").is_err());
+        assert!(parse_stimulus("0 set a yes").is_err());
+    }
+
+    #[test]
+    fn test_check_events() {
+        let graph = Graph::load("electron-architect 6\nnode 0 toggle 0 0\nlabel 0 a\n".as_bytes()).unwrap();
+        let events = parse_stimulus("0 set a 1\n3 expect a 1\n").unwrap();
+        assert_eq!(check_events(&graph, &events, 4), Ok(()));
+        assert!(check_events(&graph, &events, 3).unwrap_err().contains("tick 3"), "events past --ticks shouldn't be dropped");
+        assert!(check_events(&graph, &parse_stimulus("0 set b 1").unwrap(), 4).unwrap_err().contains("`b`"));
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("q"), "\"q\"");
+        assert_eq!(json_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+    }
+}
@@ -0,0 +1,69 @@
+use raylib::prelude::*;
+use electron_architect_rs_2::graph::Graph;
+
+/// Paces the simulation against real time
+pub struct SimulationClock {
+    pub paused: bool,
+    ticks_per_second: u32,
+    /// Fraction of a tick carried over between frames
+    pending: f32,
+}
+
+impl SimulationClock {
+    const MIN_SPEED: u32 = 1;
+    const MAX_SPEED: u32 = 1024;
+    /// Keeps a slow frame from snowballing into an even slower one
+    const MAX_TICKS_PER_FRAME: u32 = 4096;
+    const FONT_SIZE: i32 = 20;
+    const MARGIN: i32 = 6;
+
+    pub fn new() -> Self {
+        Self {
+            paused: false,
+            ticks_per_second: 16,
+            pending: 0.0,
+        }
+    }
+
+    pub fn ticks_per_second(&self) -> u32 {
+        self.ticks_per_second
+    }
+
+    /// Space pauses, period steps once while paused, plus and minus double and halve the speed
+    pub fn handle_input(&mut self, rl: &RaylibHandle, graph: &mut Graph) {
+        if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
+            self.paused = !self.paused;
+            self.pending = 0.0;
+        }
+        if self.paused && rl.is_key_pressed(KeyboardKey::KEY_PERIOD) {
+            graph.step();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_EQUAL) || rl.is_key_pressed(KeyboardKey::KEY_KP_ADD) {
+            self.ticks_per_second = (self.ticks_per_second * 2).min(Self::MAX_SPEED);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_MINUS) || rl.is_key_pressed(KeyboardKey::KEY_KP_SUBTRACT) {
+            self.ticks_per_second = (self.ticks_per_second / 2).max(Self::MIN_SPEED);
+        }
+    }
+
+    /// Runs however many ticks fit in the time since the last frame.
+    pub fn update(&mut self, graph: &mut Graph, frame_time: f32) {
+        if self.paused {
+            return;
+        }
+        self.pending += frame_time * self.ticks_per_second as f32;
+        let ticks = (self.pending.floor() as u32).min(Self::MAX_TICKS_PER_FRAME);
+        self.pending = self.pending.fract();
+        graph.run(ticks as u64);
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, graph: &Graph) {
+        let text = if self.paused {
+            format!("paused  tick {}", graph.tick())
+        } else {
+            format!("{} ticks/s  tick {}", self.ticks_per_second, graph.tick())
+        };
+        let x = d.get_screen_width() - d.measure_text(&text, Self::FONT_SIZE) - Self::MARGIN;
+        d.draw_text(&text, x, Self::MARGIN, Self::FONT_SIZE, Color::LIGHTGRAY);
+    }
+}
@@ -1,30 +1,109 @@
-use std::{cell::RefCell, rc::{Rc, Weak}};
+use std::collections::BTreeMap;
+use arena::Arena;
+use component::{Component, ComponentId};
 use node::{gate::Gate, Node};
-use quad_tree::{InfiniteQuadTree, Positioned};
+use quad_tree::{Entry, InfiniteQuadTree, Positioned};
 use raylib::{prelude::{Color, RaylibDraw, Rectangle, Vector2}};
-use wire::Wire;
+use signal::Signal;
+use wire::{ElbowHandle, ElbowOrientation, Wire, WireError, WireHandle};
 use crate::vector2i::Vector2i;
 
+pub use arena::NodeId;
+
+pub mod arena;
+pub mod component;
+pub mod history;
 pub mod node;
 pub mod wire;
 pub mod quad_tree;
+pub mod router;
+pub mod save;
+pub mod signal;
+
+/// A node's entry in the spatial index
+#[derive(Debug, Clone, Copy)]
+struct Located {
+    id: NodeId,
+    position: Vector2i,
+}
 
-impl Positioned for Rc<RefCell<Node>> {
+impl Positioned for Located {
     fn position(&self) -> Vector2i {
-        self.borrow().position
+        self.position
     }
+
+    fn set_position(&mut self, position: Vector2i) {
+        self.position = position;
+    }
+}
+
+/// Whether the two circuits save to the same text
+fn same_circuit(a: &Graph, b: &Graph) -> bool {
+    let (mut a_text, mut b_text) = (Vec::new(), Vec::new());
+    a.save(&mut a_text).expect("writing to memory cannot fail");
+    b.save(&mut b_text).expect("writing to memory cannot fail");
+    a_text == b_text
+}
+
+/// Slides an elbow that lines up with a node moving from `from` to `to` along with it,
+/// keeping the segment between them straight
+fn follow(elbow: &mut Vector2i, from: Vector2i, to: Vector2i) {
+    if elbow.x == from.x {
+        elbow.x = to.x;
+    }
+    if elbow.y == from.y {
+        elbow.y = to.y;
+    }
+}
+
+/// Distance from `point` to the closest point on the segment from `a` to `b`
+fn distance_to_segment(point: Vector2, a: Vector2, b: Vector2) -> f32 {
+    let ab = b - a;
+    let length_sqr = ab.length_sqr();
+    let t = if length_sqr == 0.0 { 0.0 } else { ((point - a).dot(ab) / length_sqr).clamp(0.0, 1.0) };
+    point.distance_to(a + ab * t)
 }
 
+#[derive(Debug, Clone)]
 pub struct Graph {
-    nodes: InfiniteQuadTree<Rc<RefCell<Node>>>,
+    nodes: Arena<Node>,
+    /// Finds nodes by cell; kept in step with each node's position
+    cells: InfiniteQuadTree<Located>,
+    /// Nodes whose inputs changed and need to be re-evaluated on the next tick
+    dirty: Vec<NodeId>,
+    tick: u64,
+    /// Whether node ranks reflect the current wiring
+    levelized: bool,
+    /// Components that can be placed in this graph. They are never removed, so their IDs stay valid.
+    components: Vec<Component>,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Graph {
     pub const GRID_SIZE: f32 = 15.0;
 
+    /// Color of a high signal
+    pub const ON_COLOR: Color = Color::new(255, 70, 50, 255);
+    /// Color of a low signal
+    pub const OFF_COLOR: Color = Color::new(80, 80, 80, 255);
+
+    pub fn state_color(state: bool) -> Color {
+        if state { Self::ON_COLOR } else { Self::OFF_COLOR }
+    }
+
     pub fn new() -> Self {
         Self {
-            nodes: InfiniteQuadTree::new(),
+            nodes: Arena::new(),
+            cells: InfiniteQuadTree::new(),
+            dirty: Vec::new(),
+            tick: 0,
+            levelized: false,
+            components: Vec::new(),
         }
     }
 
@@ -52,83 +131,1001 @@ impl Graph {
         )
     }
 
-    pub fn node_world_centered(&self, node: &Rc<RefCell<Node>>) -> Vector2 {
-        self.grid_to_world_centered(node.borrow().position)
+    pub fn node_world_centered(&self, node: &Node) -> Vector2 {
+        self.grid_to_world_centered(node.position)
+    }
+
+    /// The node, unless it has been removed
+    pub fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id)
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.nodes.contains(id)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Every node along with its ID, in no particular order
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes.iter()
+    }
+
+    /// Makes the component available to place as [`Gate::Component`], unless one with the same name already is,
+    /// in which case that one's ID is returned instead.
+    pub fn add_component(&mut self, component: Component) -> ComponentId {
+        if let Some(id) = self.find_component(&component.name) {
+            return id;
+        }
+        self.components.push(component);
+        ComponentId(u32::try_from(self.components.len() - 1).expect("too many components"))
+    }
+
+    /// Like [`Graph::add_component`], except that a component with the same name but a different circuit replaces
+    /// the graph's copy. Nodes placed from the old copy get a fresh copy of the new circuit, with the wires into them
+    /// dealt out to its pins the way [`Graph::set_gate`] does.
+    /// Fails without changing anything if a node's wires don't fit the new pins.
+    pub fn update_component(&mut self, component: Component) -> Result<ComponentId, WireError> {
+        let Some(id) = self.find_component(&component.name) else {
+            return Ok(self.add_component(component));
+        };
+        let index = id.0 as usize;
+        if same_circuit(&self.components[index].circuit, &component.circuit) {
+            return Ok(id);
+        }
+
+        let old = std::mem::replace(&mut self.components[index], component);
+        let gate = Gate::Component(id);
+        let placed: Vec<NodeId> = self.nodes.iter().filter(|(_, node)| node.gate == gate).map(|(id, _)| id).collect();
+        match placed.iter().map(|&node| self.ports_for_gate(node, gate)).collect::<Result<Vec<_>, _>>() {
+            Ok(ports) => {
+                for (node, ports) in placed.into_iter().zip(ports) {
+                    self.set_gate_with_ports(node, gate, &ports);
+                }
+                Ok(id)
+            },
+            Err(e) => {
+                self.components[index] = old;
+                Err(e)
+            },
+        }
+    }
+
+    pub fn component(&self, id: ComponentId) -> Option<&Component> {
+        self.components.get(id.0 as usize)
+    }
+
+    pub fn find_component(&self, name: &str) -> Option<ComponentId> {
+        self.components
+            .iter()
+            .position(|component| component.name == name)
+            .map(|index| ComponentId(index as u32))
+    }
+
+    /// How many wires a node with the gate can take
+    pub fn max_inputs(&self, gate: Gate) -> usize {
+        match gate {
+            Gate::Component(_) => self.input_count(gate),
+            _ => gate.max_inputs(),
+        }
+    }
+
+    /// How many input ports a node with the gate has for wires to drive
+    pub fn input_count(&self, gate: Gate) -> usize {
+        match gate {
+            Gate::Component(id) => self.component(id).map_or(0, Component::input_count),
+            _ => gate.input_ports().len(),
+        }
+    }
+
+    /// How many output ports a node with the gate has for wires to read
+    pub fn output_count(&self, gate: Gate) -> usize {
+        match gate {
+            Gate::Component(id) => self.component(id).map_or(0, Component::output_count),
+            _ => gate.output_ports().len(),
+        }
+    }
+
+    /// Name of the `port`th input port of a node with the gate; components' ports are named after their pins.
+    pub fn input_port_name(&self, gate: Gate, port: usize) -> Option<&str> {
+        match gate {
+            Gate::Component(id) => self.component(id)?.input_name(port),
+            _ => gate.input_ports().get(port).copied(),
+        }
+    }
+
+    /// Name of the `port`th output port of a node with the gate; components' ports are named after their pins.
+    pub fn output_port_name(&self, gate: Gate, port: usize) -> Option<&str> {
+        match gate {
+            Gate::Component(id) => self.component(id)?.output_name(port),
+            _ => gate.output_ports().get(port).copied(),
+        }
+    }
+
+    /// Name of the gate, or of the component it places
+    pub fn gate_name(&self, gate: Gate) -> &str {
+        match gate {
+            Gate::Component(id) => self.component(id).map_or(gate.name(), Component::name),
+            _ => gate.name(),
+        }
+    }
+
+    /// Signal on one of the node's outputs as of the most recent tick; low if the node or output doesn't exist.
+    pub fn output_value(&self, id: NodeId, port: usize) -> Signal {
+        let Some(node) = self.nodes.get(id) else {
+            return Signal::LOW;
+        };
+        match (node.gate, node.circuit.as_deref()) {
+            (Gate::Component(component), Some(circuit)) => self
+                .component(component)
+                .and_then(|component| component.outputs.get(port))
+                .and_then(|&pin| circuit.node(pin))
+                .map_or(Signal::LOW, Node::value),
+            // Each output of a splitter reads one bit of the bus it holds
+            (Gate::Split(width), _) if port < width as usize => Signal::bit(node.state.get(port)),
+            (Gate::Split(_), _) => Signal::LOW,
+            _ if port == 0 => node.state,
+            _ => Signal::LOW,
+        }
+    }
+
+    /// Whether one of the node's outputs is high as of the most recent tick, as [`Signal::is_high`] reads it
+    pub fn output_state(&self, id: NodeId, port: usize) -> bool {
+        self.output_value(id, port).is_high()
+    }
+
+    /// A fresh copy of the circuit behind the gate, for a node being given that gate
+    fn instantiate(&self, gate: Gate) -> Option<Box<Graph>> {
+        let Gate::Component(id) = gate else {
+            return None;
+        };
+        self.component(id).map(|component| Box::new(component.circuit.clone()))
     }
 
     fn reset_visited(&mut self) {
-        for node in self.nodes.iter() {
-            node.borrow_mut().visited = false;
+        for (_, node) in self.nodes.iter_mut() {
+            node.visited = false;
+        }
+    }
+
+    /// Number of ticks simulated so far
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Whether there are no pending changes left to propagate
+    pub fn is_stable(&self) -> bool {
+        self.dirty.is_empty()
+    }
+
+    /// Queues the node for re-evaluation on the next tick, if it isn't already.
+    fn schedule(&mut self, id: NodeId) {
+        if let Some(node) = self.nodes.get_mut(id) {
+            if !node.queued {
+                node.queued = true;
+                self.dirty.push(id);
+            }
         }
     }
 
+    /// Queues every node for re-evaluation, then advances one tick.
     pub fn evaluate_all(&mut self) {
-        for node in self.nodes.iter() {
-            let state = node
-                .borrow()
-                .evaluate();
+        let ids: Vec<_> = self.nodes.ids().collect();
+        for id in ids {
+            self.schedule(id);
+        }
+        self.step();
+    }
+
+    /// Computes what the node's output should become given the current state of its inputs.
+    /// Does not modify the node's own state.
+    fn evaluate(&self, node: &Node) -> Signal {
+        if let Some(forced) = node.forced {
+            return forced;
+        }
+        let input_values = node.inputs
+            .iter()
+            .filter(|wire| self.nodes.contains(wire.input))
+            .map(|wire| (wire.input_port, self.output_value(wire.input, wire.output_port)));
+
+        node.gate.evaluate(input_values)
+    }
+
+    /// Assigns every node a rank such that each wire not closing a cycle leads to a higher rank.
+    ///
+    /// Ranks are the reverse postorder of a depth-first walk along outputs, starting from nodes without inputs.
+    /// Uses an explicit stack so that long chains can't overflow.
+    fn levelize(&mut self) {
+        self.reset_visited();
+        let mut roots: Vec<NodeId> = self.nodes.ids().collect();
+        // Stable sort keeps the rest of the order deterministic
+        roots.sort_by_key(|&id| !self.nodes[id].inputs.is_empty());
+
+        let mut postorder = Vec::with_capacity(roots.len());
+        let mut stack: Vec<(NodeId, usize)> = Vec::new();
+        for root in roots {
+            if self.nodes[root].visited {
+                continue;
+            }
+            self.nodes[root].visited = true;
+            stack.push((root, 0));
+            while let Some((id, index)) = stack.last_mut() {
+                let next = self.nodes[*id].outputs.get(*index).copied();
+                *index += 1;
+                match next {
+                    Some(next) => {
+                        if let Some(next_node) = self.nodes.get_mut(next) {
+                            if !next_node.visited {
+                                next_node.visited = true;
+                                stack.push((next, 0));
+                            }
+                        }
+                    },
+                    None => {
+                        let (id, _) = stack.pop().expect("stack should be non-empty inside loop");
+                        postorder.push(id);
+                    },
+                }
+            }
+        }
+
+        for (rank, &id) in postorder.iter().rev().enumerate() {
+            self.nodes[id].rank = rank;
+        }
+        self.levelized = true;
+    }
+
+    /// Advances the simulation by one tick.
+    ///
+    /// Queued nodes are evaluated in rank order, and a change is propagated through the rest of the circuit within the same tick.
+    /// The exception is a wire closing a cycle: the node on the far end sees the change on the next tick instead,
+    /// so feedback loops hold state between ticks the way latches do in hardware.
+    pub fn step(&mut self) {
+        if !self.levelized {
+            self.levelize();
+        }
+
+        let mut pending: BTreeMap<usize, NodeId> = BTreeMap::new();
+        for id in std::mem::take(&mut self.dirty) {
+            // Nodes removed since they were queued have nothing left to update
+            if let Some(node) = self.nodes.get_mut(id) {
+                node.queued = false;
+                pending.insert(node.rank, id);
+            }
+        }
+
+        let mut next_tick = Vec::new();
+        while let Some((rank, id)) = pending.pop_first() {
+            let changed = if self.nodes[id].circuit.is_some() {
+                let (changed, settled) = self.step_component(id);
+                if !settled {
+                    // The component's circuit still has changes in flight, which carry on next tick
+                    next_tick.push(id);
+                }
+                changed
+            } else {
+                let state = self.evaluate(&self.nodes[id]);
+                let changed = self.nodes[id].state != state;
+                self.nodes[id].state = state;
+                changed
+            };
+            if !changed {
+                continue;
+            }
+            for &output in self.nodes[id].outputs.iter() {
+                let Some(output_node) = self.nodes.get(output) else { continue };
+                if output != id && output_node.rank > rank {
+                    pending.entry(output_node.rank).or_insert(output);
+                } else {
+                    next_tick.push(output);
+                }
+            }
+        }
+        for id in next_tick {
+            self.schedule(id);
         }
+
+        self.tick += 1;
+    }
+
+    /// Drives the inputs of the component node's circuit from the wires into the node, then advances that circuit by one tick.
+    /// Returns whether any of the node's outputs changed, and whether its circuit settled.
+    fn step_component(&mut self, id: NodeId) -> (bool, bool) {
+        let node = &self.nodes[id];
+        let Some(component) = (match node.gate { Gate::Component(component) => self.components.get(component.0 as usize), _ => None }) else {
+            return (false, true);
+        };
+        // Inputs without a wire read low
+        let mut inputs = vec![Signal::LOW; component.inputs.len()];
+        for wire in node.inputs.iter() {
+            if let Some(input) = inputs.get_mut(wire.input_port) {
+                *input = self.output_value(wire.input, wire.output_port);
+            }
+        }
+        let node = &mut self.nodes[id];
+        let Some(circuit) = node.circuit.as_deref_mut() else {
+            return (false, true);
+        };
+        // Forcing the pins rather than driving them lets whole buses through
+        for (&pin, &input) in component.inputs.iter().zip(inputs.iter()) {
+            circuit.force(pin, input);
+        }
+        let outputs = |circuit: &Graph| -> Vec<Signal> {
+            component.outputs.iter().map(|&pin| circuit.node(pin).map_or(Signal::LOW, Node::value)).collect()
+        };
+        let before = outputs(circuit);
+        circuit.step();
+        let after = outputs(circuit);
+        node.state = after.first().copied().unwrap_or_default();
+        (before != after, circuit.is_stable())
+    }
+
+    /// Advances the simulation by `ticks` ticks.
+    pub fn run(&mut self, ticks: u64) {
+        for i in 0..ticks {
+            if self.is_stable() {
+                // Nothing left to propagate; skip ahead
+                self.tick += ticks - i;
+                return;
+            }
+            self.step();
+        }
+    }
+
+    /// Steps until there is nothing left to propagate, giving up after `max_ticks`.
+    /// Returns whether the graph settled; circuits that oscillate never do.
+    pub fn settle(&mut self, max_ticks: u64) -> bool {
+        for _ in 0..max_ticks {
+            if self.is_stable() {
+                return true;
+            }
+            self.step();
+        }
+        self.is_stable()
     }
 
-    pub fn put_node(&mut self, gate: Gate, position: Vector2i) -> Weak<RefCell<Node>> {
-        let new_node = Node::new(gate, position);
-        let node_rc = Rc::new(RefCell::new(new_node));
-        self.nodes.insert(node_rc);
-        if let Some(node_ref) = self.nodes.iter().last() {
-            Rc::downgrade(node_ref)
-        } else {
-            Weak::new()
+    /// Makes the node output `value` regardless of its gate and inputs, queuing it if that's a change.
+    fn force(&mut self, id: NodeId, value: Signal) {
+        let Some(node) = self.nodes.get_mut(id) else { return };
+        if node.forced != Some(value) {
+            node.forced = Some(value);
+            self.schedule(id);
         }
     }
 
-    pub fn find_node_at(&self, position: Vector2i) -> Option<Weak<RefCell<Node>>> {
+    /// Switches an input node (see `Gate::is_input`) to output `value`.
+    /// Returns `false` without changing anything if the node's gate is computed from its inputs instead, or the node is gone.
+    pub fn drive(&mut self, id: NodeId, value: bool) -> bool {
+        let Some(gate) = self.nodes.get(id).and_then(|node| node.gate.driven(value)) else {
+            return false;
+        };
+        self.nodes[id].gate = gate;
+        self.schedule(id);
+        true
+    }
+
+    /// Replaces the node's gate and queues it for re-evaluation, moving its wires to the new gate's input ports
+    /// as [`Graph::ports_for_gate`] describes.
+    /// Fails without changing anything if the node has more inputs than the new gate can take.
+    pub fn set_gate(&mut self, id: NodeId, gate: Gate) -> Result<(), WireError> {
+        let ports = self.ports_for_gate(id, gate)?;
+        self.set_gate_with_ports(id, gate, &ports);
+        Ok(())
+    }
+
+    /// Checks whether [`Graph::set_gate`] would succeed, without changing anything.
+    pub fn can_set_gate(&self, id: NodeId, gate: Gate) -> Result<(), WireError> {
+        self.ports_for_gate(id, gate).map(|_| ())
+    }
+
+    /// The input port each wire into the node would go to if it had the new gate, in the order the wires were connected.
+    /// Wires keep their ports if the new gate has them and no two wires share one it only takes one wire into;
+    /// otherwise they're dealt out to its ports in order.
+    ///
+    /// Fails if the node has more inputs than the new gate can take, or a wire out of it reads an output the new gate doesn't have.
+    pub fn ports_for_gate(&self, id: NodeId, gate: Gate) -> Result<Vec<usize>, WireError> {
+        let node = self.nodes.get(id).ok_or(WireError::DanglingNode)?;
+        let max = self.max_inputs(gate);
+        if node.inputs.len() > max {
+            return Err(WireError::InputLimitExceeded { gate, max });
+        }
+        let outputs = self.output_count(gate);
+        let reads_missing_output = node.outputs
+            .iter()
+            .filter_map(|&output| self.nodes.get(output))
+            .flat_map(|output| output.inputs.iter())
+            .any(|wire| wire.input == id && wire.output_port >= outputs);
+        if reads_missing_output {
+            return Err(WireError::NoSuchOutput { outputs });
+        }
+
+        let ports: Vec<usize> = node.inputs.iter().map(|wire| wire.input_port).collect();
+        let inputs = self.input_count(gate);
+        let fits = ports.iter().enumerate().all(|(index, &port)| {
+            port < inputs && (gate.is_variadic() || !ports[..index].contains(&port))
+        });
+        Ok(match fits {
+            true => ports,
+            false if gate.is_variadic() => vec![0; ports.len()],
+            false => (0..ports.len()).collect(),
+        })
+    }
+
+    /// Like [`Graph::set_gate`], with the ports from [`Graph::ports_for_gate`] or ones the node had before.
+    /// The gate has to be able to take the node's wires.
+    pub(in crate::graph) fn set_gate_with_ports(&mut self, id: NodeId, gate: Gate, ports: &[usize]) {
+        let circuit = self.instantiate(gate);
+        let node = &mut self.nodes[id];
+        node.gate = gate;
+        node.circuit = circuit;
+        for (wire, &port) in node.inputs.iter_mut().zip(ports) {
+            wire.input_port = port;
+        }
+        self.schedule(id);
+    }
+
+    /// Places a new node, removing any node already in the cell along with its wires.
+    pub fn put_node(&mut self, gate: Gate, position: Vector2i) -> NodeId {
+        if let Some(existing) = self.find_node_at(position) {
+            self.remove_node(existing);
+        }
+        self.add_node(Node::new(gate, position))
+    }
+
+    pub fn find_node_at(&self, position: Vector2i) -> Option<NodeId> {
+        self.cells.at(position).map(|cell| cell.id)
+    }
+
+    /// The node closest to the cell, if any is within `max_distance` cells of it
+    pub fn find_nearest_node(&self, position: Vector2i, max_distance: f32) -> Option<NodeId> {
+        self.cells
+            .nearest(position)
+            .filter(|cell| Vector2::from(cell.position - position).length() <= max_distance)
+            .map(|cell| cell.id)
+    }
+
+    /// Moves the node to another cell, keeping its wires.
+    /// Returns `false` if the cell is taken by another node or the node isn't in this graph.
+    pub fn move_node(&mut self, id: NodeId, position: Vector2i) -> bool {
+        let Some(node) = self.nodes.get(id) else {
+            return false;
+        };
+        self.move_nodes(&[id], position - node.position)
+    }
+
+    /// Moves every node by `offset`, along with the elbows of wires running between two of them.
+    /// Wires with only one end moving keep their shape apart from the elbow next to that end,
+    /// which slides along with it so the wire still leaves the node in a straight line.
+    /// Returns `false` without moving anything if a node isn't in this graph
+    /// or would land on a node that isn't being moved.
+    pub fn move_nodes(&mut self, ids: &[NodeId], offset: Vector2i) -> bool {
+        let is_moving = |id: NodeId| ids.contains(&id);
+        let fits = ids.iter().all(|&id| {
+            self.nodes.get(id).is_some_and(|node| self.cells.at(node.position + offset).is_none_or(|cell| is_moving(cell.id)))
+        });
+        if !fits {
+            return false;
+        }
+
+        let followed: Vec<_> = self.crossing_wires(ids)
+            .into_iter()
+            .map(|wire| {
+                let elbows = self.followed_elbows(&wire, ids, offset);
+                (wire, elbows)
+            })
+            .collect();
+
+        // Take them all out first so they can't collide with each other on the way
+        let removed: Vec<_> = ids.iter().filter_map(|&id| self.cells.remove(self.nodes[id].position)).collect();
+        for mut cell in removed {
+            cell.position = cell.position + offset;
+            let node = &mut self.nodes[cell.id];
+            node.position = cell.position;
+            for wire in node.inputs.iter_mut() {
+                if is_moving(wire.input) {
+                    for elbow in wire.elbows.iter_mut() {
+                        *elbow = *elbow + offset;
+                    }
+                }
+            }
+            self.cells.insert(cell);
+        }
+        for (wire, elbows) in followed {
+            self.with_wire(&wire, |wire| wire.elbows = elbows);
+        }
+        true
+    }
+
+    /// Wires with one end among the nodes and the other outside them
+    pub(in crate::graph) fn crossing_wires(&self, ids: &[NodeId]) -> Vec<WireHandle> {
+        let mut wires = self.wires_of(ids.iter().copied());
+        wires.retain(|wire| ids.contains(&wire.input) != ids.contains(&wire.output));
+        wires
+    }
+
+    /// The elbows a wire would have once the nodes are moved by `offset`, as [`Graph::move_nodes`] leaves them
+    pub(in crate::graph) fn followed_elbows(&self, wire: &WireHandle, ids: &[NodeId], offset: Vector2i) -> Vec<Vector2i> {
+        let (Some(found), Some(input), Some(output)) = (self.find_wire(wire), self.nodes.get(wire.input), self.nodes.get(wire.output)) else {
+            return Vec::new();
+        };
+        let mut elbows = found.elbows.clone();
+        let input_moves = ids.contains(&wire.input);
+        if input_moves && ids.contains(&wire.output) {
+            return elbows.into_iter().map(|elbow| elbow + offset).collect();
+        }
+        if let Some(first) = elbows.first_mut().filter(|_| input_moves) {
+            follow(first, input.position, input.position + offset);
+        }
+        if let Some(last) = elbows.last_mut().filter(|_| ids.contains(&wire.output)) {
+            follow(last, output.position, output.position + offset);
+        }
+        elbows
+    }
+
+    /// Nodes within the inclusive range of cells
+    pub fn nodes_in_rect(&self, min: Vector2i, max: Vector2i) -> impl Iterator<Item = NodeId> + '_ {
+        self.cells.query_rect(min, max).map(|cell| cell.id)
+    }
+
+    /// Range of cells overlapping the world space rectangle
+    fn cells_overlapping(&self, rec: Rectangle) -> (Vector2i, Vector2i) {
+        (
+            self.world_to_grid(Vector2::new(rec.x, rec.y)),
+            self.world_to_grid(Vector2::new(rec.x + rec.width, rec.y + rec.height)),
+        )
+    }
+
+    pub fn find_node_by_label(&self, label: &str) -> Option<NodeId> {
         self.nodes
             .iter()
-            .find_map(|node|
-                (node.borrow().position == position)
-                    .then(|| Rc::downgrade(node))
-            )
+            .find_map(|(id, node)| (node.label() == Some(label)).then_some(id))
+    }
+
+    /// Every labeled node, sorted by label.
+    pub fn labeled_nodes(&self) -> Vec<NodeId> {
+        let mut labeled: Vec<_> = self.nodes
+            .iter()
+            .filter(|(_, node)| node.label.is_some())
+            .map(|(id, _)| id)
+            .collect();
+        labeled.sort_by(|&a, &b| self.nodes[a].label.cmp(&self.nodes[b].label));
+        labeled
+    }
+
+    /// Names the node, or clears its name with `None`.
+    /// Returns `false` without changing anything if the label is not a single word, another node already has it,
+    /// or the node is gone.
+    pub fn set_label(&mut self, id: NodeId, label: Option<String>) -> bool {
+        if let Some(label) = label.as_deref() {
+            let taken = self.find_node_by_label(label).is_some_and(|other| other != id);
+            if !Node::is_valid_label(label) || taken {
+                return false;
+            }
+        }
+        let Some(node) = self.nodes.get_mut(id) else {
+            return false;
+        };
+        node.label = label;
+        true
+    }
+
+    /// Inserts the node and returns its ID.
+    /// The cell must be free.
+    fn add_node(&mut self, mut node: Node) -> NodeId {
+        node.circuit = self.instantiate(node.gate);
+        let position = node.position;
+        let Entry::Vacant(entry) = self.cells.entry(position) else {
+            panic!("a node is already at {position:?}");
+        };
+        let id = self.nodes.insert(node);
+        entry.insert(Located { id, position });
+        self.schedule(id);
+        self.levelized = false;
+        id
+    }
+
+    /// Whether the input port can take another wire
+    fn is_input_port_free(&self, node: &Node, port: usize) -> bool {
+        node.gate.is_variadic() || node.inputs.iter().all(|wire| wire.input_port != port)
+    }
+
+    /// The first of the node's input ports that can take another wire
+    pub fn free_input_port(&self, id: NodeId) -> Option<usize> {
+        let node = self.nodes.get(id)?;
+        (0..self.input_count(node.gate)).find(|&port| self.is_input_port_free(node, port))
+    }
+
+    /// The input port closest to the world space point out of those that can take another wire
+    pub fn free_input_port_near(&self, id: NodeId, point: Vector2) -> Option<usize> {
+        let node = self.nodes.get(id)?;
+        (0..self.input_count(node.gate))
+            .filter(|&port| self.is_input_port_free(node, port))
+            .min_by(|&a, &b| {
+                let distance = |port| self.input_port_position(node, port).distance_to(point);
+                distance(a).total_cmp(&distance(b))
+            })
+    }
+
+    /// The output port closest to the world space point
+    pub fn output_port_near(&self, id: NodeId, point: Vector2) -> Option<usize> {
+        let node = self.nodes.get(id)?;
+        (0..self.output_count(node.gate)).min_by(|&a, &b| {
+            let distance = |port| self.output_port_position(node, port).distance_to(point);
+            distance(a).total_cmp(&distance(b))
+        })
     }
 
-    /// Wires cannot be created from nodes already known to be dropped.
-    pub fn wire(&mut self, input: Rc<RefCell<Node>>, output: Rc<RefCell<Node>>) {
-        let p1 = input.borrow().position;
-        let p2 = output.borrow().position;
-        let elbow = Vector2i::new(p2.x, p1.y);
-        let new_wire = Wire::new(&input, &output, Vec::from([elbow]));
-        output.borrow_mut().inputs.push(new_wire);
+    /// Checks whether a wire from `input` to `output` would be valid, without creating it.
+    /// The wire reads the first output of `input` and drives the first free input of `output`.
+    pub fn can_wire(&self, input: NodeId, output: NodeId) -> Result<(), WireError> {
+        self.can_wire_ports(input, 0, output, self.free_input_port(output).unwrap_or(0))
+    }
+
+    /// Like [`Graph::can_wire`], for a wire from the `output_port`th output of `input` to the `input_port`th input of `output`
+    pub fn can_wire_ports(&self, input: NodeId, output_port: usize, output: NodeId, input_port: usize) -> Result<(), WireError> {
+        let (Some(input_node), Some(output_node)) = (self.nodes.get(input), self.nodes.get(output)) else {
+            return Err(WireError::DanglingNode);
+        };
+        if input == output {
+            return Err(WireError::SelfLoop);
+        }
+        let handle = WireHandle { input, output_port, output, input_port };
+        if output_node.inputs.iter().any(|wire| wire.handle(output) == handle) {
+            return Err(WireError::Duplicate);
+        }
+        let max = self.max_inputs(output_node.gate);
+        if output_node.inputs.len() >= max {
+            return Err(WireError::InputLimitExceeded { gate: output_node.gate, max });
+        }
+        let outputs = self.output_count(input_node.gate);
+        if output_port >= outputs {
+            return Err(WireError::NoSuchOutput { outputs });
+        }
+        let inputs = self.input_count(output_node.gate);
+        if input_port >= inputs {
+            return Err(WireError::NoSuchInput { inputs });
+        }
+        if !self.is_input_port_free(output_node, input_port) {
+            return Err(WireError::PortTaken);
+        }
+        Ok(())
+    }
+
+    /// Wires `input` into `output` with a single bend, between the ports [`Graph::can_wire`] picks.
+    pub fn wire(&mut self, input: NodeId, output: NodeId) -> Result<(), WireError> {
+        self.can_wire(input, output)?;
+        let elbow = ElbowOrientation::default().corner(self.nodes[input].position, self.nodes[output].position);
+        self.wire_along(input, output, Vec::from([elbow]))
+    }
+
+    /// Like [`Graph::wire`], but routed through the given elbows instead of a single bend.
+    pub fn wire_along(&mut self, input: NodeId, output: NodeId, elbows: Vec<Vector2i>) -> Result<(), WireError> {
+        let input_port = self.free_input_port(output).unwrap_or(0);
+        self.wire_ports_along(input, 0, output, input_port, elbows)
+    }
+
+    /// Like [`Graph::wire_along`], from the `output_port`th output of `input` to the `input_port`th input of `output`
+    pub fn wire_ports_along(&mut self, input: NodeId, output_port: usize, output: NodeId, input_port: usize, elbows: Vec<Vector2i>) -> Result<(), WireError> {
+        self.can_wire_ports(input, output_port, output, input_port)?;
+        self.connect(input, output_port, output, input_port, elbows);
+        Ok(())
+    }
+
+    /// Elbows for a wire from `input` to `output` that steer around every other node, with as few bends as possible.
+    /// Falls back to a single bend if no such route is found nearby, and to none if either node is gone.
+    pub fn route_wire(&self, input: NodeId, output: NodeId) -> Vec<Vector2i> {
+        let (Some(input), Some(output)) = (self.nodes.get(input), self.nodes.get(output)) else {
+            return Vec::new();
+        };
+        self.route_between(input.position, output.position)
+    }
+
+    /// Elbows of a path between two cells that goes around nodes, like [`Graph::route_wire`]
+    pub fn route_between(&self, start: Vector2i, end: Vector2i) -> Vec<Vector2i> {
+        router::route(start, end, |cell| self.cells.at(cell).is_some())
+            .unwrap_or_else(|| Vec::from([ElbowOrientation::default().corner(start, end)]))
+    }
+
+    /// Re-routes every wire into or out of the given nodes with [`Graph::route_wire`].
+    pub fn reroute_wires(&mut self, ids: impl IntoIterator<Item = NodeId>) {
+        for wire in self.wires_of(ids) {
+            self.reroute_wire(&wire);
+        }
+    }
+
+    /// Every wire into or out of the given nodes, once each
+    fn wires_of(&self, ids: impl IntoIterator<Item = NodeId>) -> Vec<WireHandle> {
+        let mut wires = Vec::new();
+        for id in ids {
+            let Some(node) = self.nodes.get(id) else { continue };
+            wires.extend(node.inputs.iter().map(|wire| wire.handle(id)));
+            for &output in node.outputs.iter() {
+                let Some(output_node) = self.nodes.get(output) else { continue };
+                wires.extend(output_node.inputs.iter().filter(|wire| wire.input == id).map(|wire| wire.handle(output)));
+            }
+        }
+        // Wires between two of the nodes were found from both ends
+        wires.sort();
+        wires.dedup();
+        wires
     }
 
-    pub fn draw_wires(&self, d: &mut impl RaylibDraw) {
-        for node in self.nodes.iter() {
-            let node = node.borrow();
-            let node_position = self.grid_to_world_centered(node.position);
+    /// Re-routes the wire with [`Graph::route_wire`]. Returns `false` if the wire no longer exists.
+    pub fn reroute_wire(&mut self, wire: &WireHandle) -> bool {
+        let elbows = self.route_wire(wire.input, wire.output);
+        self.with_wire(wire, |wire| wire.elbows = elbows).is_some()
+    }
+
+    /// Finds the wire passing closest to the world space point, if any passes within `tolerance` of it.
+    /// Every segment of every wire is checked.
+    pub fn find_wire_at(&self, point: Vector2, tolerance: f32) -> Option<WireHandle> {
+        let mut closest: Option<(f32, WireHandle)> = None;
+        for (id, node) in self.nodes.iter() {
             for wire in node.inputs.iter() {
-                if let Some(input) = wire.input.upgrade() {
-                    let input = input.borrow();
-                    let input_position = self.grid_to_world_centered(input.position);
-
-                    let mut points = Vec::with_capacity(wire.elbows.len() + 2);
-                    points.push(input_position);
-                    points.extend(wire.elbows.iter().map(|&p| self.grid_to_world_centered(p)));
-                    points.push(node_position);
-                    d.draw_line_strip(points.as_slice(), Color::WHITE);
+                let Some(points) = self.wire_points(wire, node) else { continue };
+                let distance = points
+                    .windows(2)
+                    .map(|segment| distance_to_segment(point, segment[0], segment[1]))
+                    .fold(f32::INFINITY, f32::min);
+                if distance <= tolerance && closest.as_ref().is_none_or(|(closest_distance, _)| distance < *closest_distance) {
+                    closest = Some((distance, wire.handle(id)));
                 }
             }
         }
+        closest.map(|(_, wire)| wire)
+    }
+
+    /// World space polyline from the wire's output port on its input through its elbows to its input port on `output`,
+    /// the node it leads into
+    fn wire_points(&self, wire: &Wire, output: &Node) -> Option<Vec<Vector2>> {
+        let input = self.nodes.get(wire.input)?;
+        let mut points = Vec::with_capacity(wire.elbows.len() + 2);
+        points.push(self.output_port_position(input, wire.output_port));
+        points.extend(wire.elbows.iter().map(|&p| self.grid_to_world_centered(p)));
+        points.push(self.input_port_position(output, wire.input_port));
+        Some(points)
     }
 
-    pub fn draw_nodes(&self, d: &mut impl RaylibDraw) {
-        for node in self.nodes.iter() {
-            let Vector2 { x, y } = self.grid_to_world(node.borrow().position);
-            d.draw_rectangle_rec(Rectangle::new(x, y, Self::GRID_SIZE, Self::GRID_SIZE), Color::WHITE);
+    /// World space point where wires into the node's `port`th input end.
+    /// Nodes with several inputs spread them down their left edge; otherwise wires meet at the center.
+    pub fn input_port_position(&self, node: &Node, port: usize) -> Vector2 {
+        self.port_position(node.position, 0.0, port, self.input_count(node.gate))
+    }
+
+    /// World space point where wires out of the node's `port`th output start.
+    /// Nodes with several outputs spread them down their right edge; otherwise wires leave from the center.
+    pub fn output_port_position(&self, node: &Node, port: usize) -> Vector2 {
+        self.port_position(node.position, Self::GRID_SIZE, port, self.output_count(node.gate))
+    }
+
+    /// The `port`th of `count` points spread evenly down the line `x` across from the cell's left edge,
+    /// or the center of the cell if there's only the one
+    fn port_position(&self, cell: Vector2i, x: f32, port: usize, count: usize) -> Vector2 {
+        if count <= 1 {
+            return self.grid_to_world_centered(cell);
         }
+        let corner = self.grid_to_world(cell);
+        Vector2::new(corner.x + x, corner.y + Self::GRID_SIZE * (port + 1) as f32 / (count + 1) as f32)
+    }
+
+    /// Finds an elbow in the cell. Elbows aren't indexed spatially, so this looks through every wire.
+    pub fn find_elbow_at(&self, position: Vector2i) -> Option<ElbowHandle> {
+        self.nodes.iter().find_map(|(id, node)| {
+            node.inputs.iter().find_map(|wire| {
+                let index = wire.elbows.iter().position(|&elbow| elbow == position)?;
+                Some(ElbowHandle { wire: wire.handle(id), index })
+            })
+        })
+    }
+
+    /// The wire, unless it no longer exists
+    fn find_wire(&self, wire: &WireHandle) -> Option<&Wire> {
+        self.nodes.get(wire.output)?.inputs.iter().find(|found| found.handle(wire.output) == *wire)
+    }
+
+    /// Runs `f` on the wire; `None` if it no longer exists.
+    fn with_wire<R>(&mut self, wire: &WireHandle, f: impl FnOnce(&mut Wire) -> R) -> Option<R> {
+        self.nodes.get_mut(wire.output)?.inputs.iter_mut().find(|found| found.handle(wire.output) == *wire).map(f)
+    }
+
+    /// Runs `f` on the wire the elbow belongs to; `None` if that wire or the elbow no longer exists.
+    fn with_elbow_wire<R>(&mut self, elbow: &ElbowHandle, f: impl FnOnce(&mut Wire) -> R) -> Option<R> {
+        self.with_wire(&elbow.wire, |wire| (elbow.index < wire.elbows.len()).then(|| f(wire))).flatten()
+    }
+
+    /// Returns `false` if the elbow no longer exists.
+    pub fn move_elbow(&mut self, elbow: &ElbowHandle, position: Vector2i) -> bool {
+        self.with_elbow_wire(elbow, |wire| wire.elbows[elbow.index] = position).is_some()
+    }
+
+    /// Straightens out the elbow. Returns `false` if it no longer exists.
+    pub fn remove_elbow(&mut self, elbow: &ElbowHandle) -> bool {
+        self.with_elbow_wire(elbow, |wire| wire.elbows.remove(elbow.index)).is_some()
     }
 
-    pub fn draw_proxy_nodes(&self, d: &mut impl RaylibDraw) {
-        for node in self.nodes.iter() {
-            let Vector2 { x, y } = self.grid_to_world(node.borrow().position);
-            d.draw_rectangle_rec(Rectangle::new(x, y, Self::GRID_SIZE, Self::GRID_SIZE), Color::WHITE);
+    /// Creates a wire along the given elbows. Both nodes must exist.
+    fn connect(&mut self, input: NodeId, output_port: usize, output: NodeId, input_port: usize, elbows: Vec<Vector2i>) {
+        self.nodes[output].inputs.push(Wire::new(input, output_port, input_port, elbows));
+        self.nodes[input].outputs.push(output);
+        self.schedule(output);
+        self.levelized = false;
+    }
+
+    /// Removes the node along with every wire into or out of it.
+    /// Returns `false` if the node was not part of the graph.
+    pub fn remove_node(&mut self, id: NodeId) -> bool {
+        let Some(node) = self.nodes.remove(id) else {
+            return false;
+        };
+        self.cells.remove(node.position);
+
+        for wire in node.inputs.iter() {
+            if let Some(input) = self.nodes.get_mut(wire.input) {
+                input.outputs.retain(|&output| output != id);
+            }
+        }
+        for &output in node.outputs.iter() {
+            if let Some(output_node) = self.nodes.get_mut(output) {
+                output_node.inputs.retain(|wire| wire.input != id);
+                self.schedule(output);
+            }
+        }
+
+        self.levelized = false;
+        true
+    }
+
+    /// Removes one wire going from `input` to `output`, whichever ports it connects.
+    /// Returns `false` if there was no such wire.
+    pub fn remove_wire(&mut self, input: NodeId, output: NodeId) -> bool {
+        let Some(wire) = self.nodes.get(output).and_then(|node| node.inputs.iter().find(|wire| wire.input == input)) else {
+            return false;
+        };
+        self.unwire(&wire.handle(output))
+    }
+
+    /// Removes the wire. Returns `false` if it no longer exists.
+    pub fn unwire(&mut self, wire: &WireHandle) -> bool {
+        let Some(output_node) = self.nodes.get_mut(wire.output) else {
+            return false;
+        };
+        let Some(index) = output_node.inputs.iter().position(|found| found.handle(wire.output) == *wire) else {
+            return false;
+        };
+        output_node.inputs.remove(index);
+        if let Some(input_node) = self.nodes.get_mut(wire.input) {
+            if let Some(index) = input_node.outputs.iter().position(|&id| id == wire.output) {
+                input_node.outputs.remove(index);
+            }
+        }
+
+        self.schedule(wire.output);
+        self.levelized = false;
+        true
+    }
+
+    /// Draws the wires that pass through `visible`, a world space rectangle, outlining the hovered and selected ones
+    /// Buses are drawn thicker than single bits.
+    pub fn draw_wires(&self, d: &mut impl RaylibDraw, visible: Rectangle, hovered: Option<&WireHandle>, selected: Option<&WireHandle>) {
+        const HIGHLIGHT_THICKNESS: f32 = 5.0;
+        const BUS_THICKNESS: f32 = 3.0;
+        let (visible_min, visible_max) = self.cells_overlapping(visible);
+        for (id, node) in self.nodes.iter() {
+            for wire in node.inputs.iter() {
+                let Some(input) = self.nodes.get(wire.input) else { continue };
+                // Wires can be long, so they're culled by their bounding box rather than looked up in the tree
+                let (mut min, mut max) = (node.position, node.position);
+                for &p in wire.elbows.iter().chain([&input.position]) {
+                    min = Vector2i::new(min.x.min(p.x), min.y.min(p.y));
+                    max = Vector2i::new(max.x.max(p.x), max.y.max(p.y));
+                }
+                if max.x < visible_min.x || visible_max.x < min.x || max.y < visible_min.y || visible_max.y < min.y {
+                    continue;
+                }
+                let Some(points) = self.wire_points(wire, node) else { continue };
+
+                let handle = wire.handle(id);
+                let highlight = if selected == Some(&handle) {
+                    Some(Color::YELLOW)
+                } else if hovered == Some(&handle) {
+                    Some(Color::WHITE)
+                } else {
+                    None
+                };
+                if let Some(color) = highlight {
+                    for segment in points.windows(2) {
+                        d.draw_line_ex(segment[0], segment[1], HIGHLIGHT_THICKNESS, color);
+                    }
+                }
+                let value = self.output_value(wire.input, wire.output_port);
+                let color = Self::state_color(value.is_high());
+                if value.width() > 1 {
+                    for segment in points.windows(2) {
+                        d.draw_line_ex(segment[0], segment[1], BUS_THICKNESS, color);
+                    }
+                } else {
+                    d.draw_line_strip(points.as_slice(), color);
+                }
+            }
+        }
+    }
+
+    /// Draws the nodes inside `visible`, a world space rectangle
+    pub fn draw_nodes(&self, d: &mut impl RaylibDraw, visible: Rectangle) {
+        const GLYPH_SIZE: i32 = 10;
+        const PIN_RADIUS: f32 = 2.0;
+        let (min, max) = self.cells_overlapping(visible);
+        for cell in self.cells.query_rect(min, max) {
+            let node = &self.nodes[cell.id];
+            let Vector2 { x, y } = self.grid_to_world(node.position);
+            let rec = Rectangle::new(x, y, Self::GRID_SIZE, Self::GRID_SIZE);
+            d.draw_rectangle_rec(rec, node.gate.color());
+            d.draw_rectangle_lines_ex(rec, 2.0, Self::state_color(node.state()));
+            let glyph = node.gate.glyph();
+            // The default font is roughly half as wide as it is tall
+            let glyph_width = GLYPH_SIZE / 2 * glyph.len() as i32;
+            let center = self.grid_to_world_centered(node.position);
+            d.draw_text(glyph, center.x as i32 - glyph_width / 2, center.y as i32 - GLYPH_SIZE / 2, GLYPH_SIZE, Color::BLACK);
+            if let Gate::Component(_) = node.gate {
+                // Components are told apart by name, written just above them
+                d.draw_text(self.gate_name(node.gate), x as i32, y as i32 - GLYPH_SIZE, GLYPH_SIZE, Color::LIGHTGRAY);
+            }
+            if node.state.width() > 1 {
+                // Buses show their value just below
+                d.draw_text(&node.state.to_string(), x as i32, y as i32 + Self::GRID_SIZE as i32 + 1, GLYPH_SIZE, Color::LIGHTGRAY);
+            }
+
+            // Wires only meet the edges of sides with several ports, so those are the ones with pins to mark
+            let inputs = self.input_count(node.gate);
+            if inputs > 1 {
+                for port in 0..inputs {
+                    let state = node.inputs
+                        .iter()
+                        .any(|wire| wire.input_port == port && self.output_state(wire.input, wire.output_port));
+                    d.draw_circle_v(self.input_port_position(node, port), PIN_RADIUS, Self::state_color(state));
+                }
+            }
+            let outputs = self.output_count(node.gate);
+            if outputs > 1 {
+                for port in 0..outputs {
+                    d.draw_circle_v(self.output_port_position(node, port), PIN_RADIUS, Self::state_color(self.output_state(cell.id, port)));
+                }
+            }
+        }
+    }
+
+    /// Writes the name of each of the node's ports beside it, inputs to the left and outputs to the right
+    pub fn draw_port_names(&self, d: &mut impl RaylibDraw, id: NodeId) {
+        const FONT_SIZE: i32 = 10;
+        const GAP: i32 = 3;
+        let Some(node) = self.nodes.get(id) else { return };
+        let left = self.grid_to_world(node.position).x as i32;
+        let right = left + Self::GRID_SIZE as i32;
+        for port in 0..self.input_count(node.gate) {
+            let Some(name) = self.input_port_name(node.gate, port) else { continue };
+            let y = self.input_port_position(node, port).y as i32 - FONT_SIZE / 2;
+            // The default font is roughly half as wide as it is tall
+            let width = FONT_SIZE / 2 * name.len() as i32;
+            d.draw_text(name, left - GAP - width, y, FONT_SIZE, Color::LIGHTGRAY);
+        }
+        for port in 0..self.output_count(node.gate) {
+            let Some(name) = self.output_port_name(node.gate, port) else { continue };
+            let y = self.output_port_position(node, port).y as i32 - FONT_SIZE / 2;
+            d.draw_text(name, right + GAP, y, FONT_SIZE, Color::LIGHTGRAY);
+        }
+    }
+
+    /// Cheaper than `draw_nodes`; shows gate types by color alone
+    pub fn draw_proxy_nodes(&self, d: &mut impl RaylibDraw, visible: Rectangle) {
+        let (min, max) = self.cells_overlapping(visible);
+        for cell in self.cells.query_rect(min, max) {
+            let node = &self.nodes[cell.id];
+            let Vector2 { x, y } = self.grid_to_world(node.position);
+            let rec = Rectangle::new(x, y, Self::GRID_SIZE, Self::GRID_SIZE);
+            d.draw_rectangle_rec(rec, node.gate.color());
+            d.draw_rectangle_lines_ex(rec, 2.0, Self::state_color(node.state()));
         }
     }
 }
@@ -140,12 +1137,501 @@ mod tests {
     #[test]
     fn test_add_wire() {
         let mut graph = Graph::new();
-        let node_1 = graph.put_node(Gate::Or, Vector2i::new(0,0));
-        let node_2 = graph.put_node(Gate::Or, Vector2i::new(1,0));
-        if let (Some(input), Some(output)) = (node_1.upgrade(), node_2.upgrade()) {
-            graph.wire(input, output);
-        } else {
-            assert!(false, "one or both weak references failed to be upgraded");
+        let input = graph.put_node(Gate::Or, Vector2i::new(0,0));
+        let output = graph.put_node(Gate::Or, Vector2i::new(1,0));
+        graph.wire(input, output).unwrap();
+    }
+
+    fn state_of(graph: &Graph, id: NodeId) -> bool {
+        graph.node(id).expect("node should still exist").state()
+    }
+
+    #[test]
+    fn test_signal_propagates_within_tick() {
+        let mut graph = Graph::new();
+        let source = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let not_1  = graph.put_node(Gate::Not,    Vector2i::new(1, 0));
+        let not_2  = graph.put_node(Gate::Not,    Vector2i::new(2, 0));
+        graph.wire(source, not_1).unwrap();
+        graph.wire(not_1, not_2).unwrap();
+
+        graph.step();
+        assert!(graph.is_stable());
+        assert!(state_of(&graph, source));
+        assert!(!state_of(&graph, not_1));
+        assert!(state_of(&graph, not_2));
+
+        graph.run(10);
+        assert_eq!(graph.tick(), 11);
+    }
+
+    #[test]
+    fn test_stable_graph_does_no_work() {
+        let mut graph = Graph::new();
+        let and = graph.put_node(Gate::And, Vector2i::new(0, 0));
+        graph.run(3);
+        assert!(graph.is_stable());
+        graph.step();
+        assert!(graph.is_stable());
+        assert_eq!(graph.tick(), 4);
+        assert!(state_of(&graph, and), "And with no inputs is vacuously true");
+    }
+
+    /// Nor-based SR latch; returns (set, reset, q, q_bar)
+    fn sr_latch(graph: &mut Graph) -> [NodeId; 4] {
+        let set   = graph.put_node(Gate::Never, Vector2i::new(0, 0));
+        let reset = graph.put_node(Gate::Never, Vector2i::new(0, 2));
+        let q_bar = graph.put_node(Gate::Nor,   Vector2i::new(2, 0));
+        let q     = graph.put_node(Gate::Nor,   Vector2i::new(2, 2));
+        graph.wire(set,   q_bar).unwrap();
+        graph.wire(reset, q).unwrap();
+        graph.wire(q,     q_bar).unwrap();
+        graph.wire(q_bar, q).unwrap();
+        [set, reset, q, q_bar]
+    }
+
+    #[test]
+    fn test_sr_latch_holds_state() {
+        let mut graph = Graph::new();
+        let [set, reset, q, q_bar] = sr_latch(&mut graph);
+        assert!(graph.settle(10), "latch should settle instead of oscillating");
+        assert_ne!(state_of(&graph, q), state_of(&graph, q_bar));
+
+        graph.set_gate(set, Gate::Always).unwrap();
+        assert!(graph.settle(10));
+        assert!(state_of(&graph, q));
+        assert!(!state_of(&graph, q_bar));
+
+        graph.set_gate(set, Gate::Never).unwrap();
+        assert!(graph.settle(10));
+        assert!(state_of(&graph, q), "latch should remember being set");
+
+        graph.set_gate(reset, Gate::Always).unwrap();
+        assert!(graph.settle(10));
+        assert!(!state_of(&graph, q));
+        assert!(state_of(&graph, q_bar));
+
+        graph.set_gate(reset, Gate::Never).unwrap();
+        assert!(graph.settle(10));
+        assert!(!state_of(&graph, q), "latch should remember being reset");
+    }
+
+    #[test]
+    fn test_ring_oscillator_toggles() {
+        let mut graph = Graph::new();
+        let ring: Vec<_> = (0..3)
+            .map(|x| graph.put_node(Gate::Not, Vector2i::new(x, 0)))
+            .collect();
+        for (&input, &output) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+            graph.wire(input, output).unwrap();
+        }
+
+        assert!(!graph.settle(20), "odd ring of inverters should never settle");
+        let mut previous = state_of(&graph, ring[0]);
+        for _ in 0..6 {
+            graph.step();
+            let current = state_of(&graph, ring[0]);
+            assert_ne!(previous, current, "oscillator should flip every tick");
+            previous = current;
         }
     }
+
+    #[test]
+    fn test_wire_rejections() {
+        let mut graph = Graph::new();
+        let source = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let not    = graph.put_node(Gate::Not,    Vector2i::new(1, 0));
+        let xor    = graph.put_node(Gate::Xor,    Vector2i::new(2, 0));
+        let and    = graph.put_node(Gate::And,    Vector2i::new(3, 0));
+
+        assert_eq!(graph.wire(not, not), Err(WireError::SelfLoop));
+        assert_eq!(graph.wire(not, source), Err(WireError::InputLimitExceeded { gate: Gate::Always, max: 0 }));
+
+        graph.wire(source, not).unwrap();
+        assert_eq!(graph.wire(source, not), Err(WireError::Duplicate));
+        assert_eq!(graph.wire(and, not), Err(WireError::InputLimitExceeded { gate: Gate::Not, max: 1 }));
+
+        graph.wire(source, xor).unwrap();
+        graph.wire(not, xor).unwrap();
+        assert_eq!(graph.wire(and, xor), Err(WireError::InputLimitExceeded { gate: Gate::Xor, max: 2 }));
+        assert_eq!(graph.nodes[xor].inputs.len(), 2, "rejected wires should not be created");
+
+        graph.wire(not, and).unwrap();
+        graph.wire(xor, and).unwrap();
+        graph.wire(source, and).unwrap();
+
+        let removed = graph.put_node(Gate::Or, Vector2i::new(4, 0));
+        graph.remove_node(removed);
+        assert_eq!(graph.wire(removed, and), Err(WireError::DanglingNode));
+        assert_eq!(graph.wire(and, removed), Err(WireError::DanglingNode));
+    }
+
+    #[test]
+    fn test_set_gate_respects_input_limit() {
+        let mut graph = Graph::new();
+        let a   = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let b   = graph.put_node(Gate::Always, Vector2i::new(0, 1));
+        let and = graph.put_node(Gate::And,    Vector2i::new(1, 0));
+        graph.wire(a, and).unwrap();
+        graph.wire(b, and).unwrap();
+
+        assert_eq!(graph.set_gate(and, Gate::Not), Err(WireError::InputLimitExceeded { gate: Gate::Not, max: 1 }));
+        assert_eq!(graph.nodes[and].gate(), Gate::And);
+        graph.set_gate(and, Gate::Xor).unwrap();
+        assert_eq!(graph.nodes[and].gate(), Gate::Xor);
+    }
+
+    #[test]
+    fn test_input_ports() {
+        let mut graph = Graph::new();
+        let a      = graph.put_node(Gate::Toggle(false), Vector2i::new(0, 0));
+        let b      = graph.put_node(Gate::Toggle(false), Vector2i::new(0, 1));
+        let select = graph.put_node(Gate::Toggle(false), Vector2i::new(0, 2));
+        let mux    = graph.put_node(Gate::Mux,           Vector2i::new(2, 1));
+        // Connected out of order; each wire goes to the port it names
+        graph.wire_ports_along(select, 0, mux, 2, Vec::new()).unwrap();
+        graph.wire_ports_along(b, 0, mux, 1, Vec::new()).unwrap();
+        assert_eq!(graph.can_wire_ports(a, 0, mux, 1), Err(WireError::PortTaken));
+        assert_eq!(graph.can_wire_ports(a, 0, mux, 3), Err(WireError::NoSuchInput { inputs: 3 }));
+        assert_eq!(graph.free_input_port(mux), Some(0));
+        graph.wire(a, mux).unwrap();
+        assert_eq!(graph.free_input_port(mux), None);
+
+        for (a_on, b_on, select_on) in [(true, false, false), (false, true, false), (true, false, true), (false, true, true)] {
+            graph.drive(a, a_on);
+            graph.drive(b, b_on);
+            graph.drive(select, select_on);
+            assert!(graph.settle(10));
+            assert_eq!(state_of(&graph, mux), if select_on { b_on } else { a_on });
+        }
+
+        // Ports are placed top to bottom, so the point nearest the bottom left corner picks `select`
+        let near_select = graph.grid_to_world(Vector2i::new(2, 2));
+        graph.unwire(&WireHandle { input: select, output_port: 0, output: mux, input_port: 2 });
+        assert_eq!(graph.free_input_port_near(mux, near_select), Some(2));
+        assert_eq!(graph.input_port_name(Gate::Mux, 2), Some("select"));
+        assert_eq!(graph.output_port_name(Gate::Mux, 0), Some("out"));
+    }
+
+    #[test]
+    fn test_set_gate_moves_ports() {
+        let mut graph = Graph::new();
+        let a   = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let b   = graph.put_node(Gate::Never,  Vector2i::new(0, 1));
+        let xor = graph.put_node(Gate::Xor,    Vector2i::new(1, 0));
+        graph.wire_ports_along(a, 0, xor, 1, Vec::new()).unwrap();
+        graph.wire_ports_along(b, 0, xor, 0, Vec::new()).unwrap();
+        let ports = |graph: &Graph| graph.nodes[xor].inputs.iter().map(|wire| wire.input_port).collect::<Vec<_>>();
+
+        assert_eq!(graph.ports_for_gate(xor, Gate::Mux), Ok(vec![1, 0]), "ports the new gate has are kept");
+        graph.set_gate(xor, Gate::Or).unwrap();
+        assert_eq!(ports(&graph), [0, 0], "an Or has one port for every wire");
+        graph.set_gate(xor, Gate::Xor).unwrap();
+        assert_eq!(ports(&graph), [0, 1], "wires are dealt out in the order they were connected");
+    }
+
+    #[test]
+    fn test_buses() {
+        let mut graph = Graph::new();
+        let bits: Vec<_> = (0..4).map(|y| graph.put_node(Gate::Toggle(false), Vector2i::new(0, y))).collect();
+        let join   = graph.put_node(Gate::Join(4),  Vector2i::new(2, 0));
+        let enable = graph.put_node(Gate::Toggle(false), Vector2i::new(2, 2));
+        let and    = graph.put_node(Gate::And,      Vector2i::new(4, 0));
+        let not    = graph.put_node(Gate::Not,      Vector2i::new(6, 0));
+        let split  = graph.put_node(Gate::Split(4), Vector2i::new(8, 0));
+        let top    = graph.put_node(Gate::Or,       Vector2i::new(10, 3));
+        for &bit in bits.iter() {
+            graph.wire(bit, join).unwrap();
+        }
+        graph.wire(join, and).unwrap();
+        graph.wire(enable, and).unwrap();
+        graph.wire(and, not).unwrap();
+        graph.wire(not, split).unwrap();
+        graph.wire_ports_along(split, 3, top, 0, Vec::new()).unwrap();
+        assert_eq!(graph.can_wire_ports(split, 4, top, 0), Err(WireError::NoSuchOutput { outputs: 4 }));
+
+        graph.drive(bits[0], true);
+        graph.drive(bits[2], true);
+        assert!(graph.settle(10));
+        assert_eq!(graph.nodes[join].value(), Signal::new(0b0101, 4));
+        assert_eq!(graph.nodes[and].value(), Signal::new(0, 4), "a low enable bit clears the whole bus");
+        assert_eq!(graph.nodes[not].value(), Signal::new(0b1111, 4), "gates work across the width of their inputs");
+
+        graph.drive(enable, true);
+        assert!(graph.settle(10));
+        assert_eq!(graph.nodes[not].value(), Signal::new(0b1010, 4));
+        let outputs: Vec<_> = (0..4).map(|port| graph.output_state(split, port)).collect();
+        assert_eq!(outputs, [false, true, false, true], "splitters give a bit per output, least significant first");
+        assert!(state_of(&graph, top));
+        assert_eq!(graph.nodes[top].value(), Signal::HIGH);
+    }
+
+    #[test]
+    fn test_long_chain_does_not_overflow() {
+        const LENGTH: i32 = 5_000;
+        let mut graph = Graph::new();
+        let source = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let mut previous = source;
+        for x in 1..=LENGTH {
+            let node = graph.put_node(Gate::Not, Vector2i::new(x, 0));
+            graph.wire(previous, node).unwrap();
+            previous = node;
+        }
+        graph.step();
+        assert!(graph.is_stable());
+        assert_eq!(state_of(&graph, previous), LENGTH % 2 == 0);
+    }
+
+    #[test]
+    fn test_remove_node_drops_its_wires() {
+        let mut graph = Graph::new();
+        let source = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let middle = graph.put_node(Gate::Or,     Vector2i::new(1, 0));
+        let sink   = graph.put_node(Gate::Or,     Vector2i::new(2, 0));
+        graph.wire(source, middle).unwrap();
+        graph.wire(middle, sink).unwrap();
+        graph.wire(source, sink).unwrap();
+        graph.settle(10);
+        assert!(state_of(&graph, sink));
+
+        assert!(graph.remove_node(source));
+        assert!(!graph.remove_node(source), "node should only be removable once");
+        assert!(graph.node(source).is_none());
+        assert!(graph.find_node_at(Vector2i::new(0, 0)).is_none());
+        assert!(graph.nodes[middle].inputs.is_empty());
+        assert_eq!(graph.nodes[sink].inputs.len(), 1);
+
+        graph.settle(10);
+        assert!(!state_of(&graph, middle));
+        assert!(!state_of(&graph, sink));
+
+        let replacement = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        assert_ne!(replacement, source, "ids of removed nodes are never handed out again");
+        assert!(graph.node(source).is_none());
+    }
+
+    #[test]
+    fn test_remove_wire() {
+        let mut graph = Graph::new();
+        let source = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let sink   = graph.put_node(Gate::Or,     Vector2i::new(1, 0));
+        graph.wire(source, sink).unwrap();
+        graph.settle(10);
+        assert!(state_of(&graph, sink));
+
+        assert!(!graph.remove_wire(sink, source), "wire direction matters");
+        assert!(graph.remove_wire(source, sink));
+        assert!(!graph.remove_wire(source, sink));
+        assert!(graph.nodes[source].outputs.is_empty());
+        assert!(graph.nodes[sink].inputs.is_empty());
+
+        graph.settle(10);
+        assert!(!state_of(&graph, sink));
+    }
+
+    #[test]
+    fn test_remove_node_in_feedback_loop() {
+        let mut graph = Graph::new();
+        let [_, _, q, q_bar] = sr_latch(&mut graph);
+        graph.settle(10);
+        assert!(graph.remove_node(q));
+        assert!(graph.nodes[q_bar].inputs.iter().all(|wire| wire.input != q));
+        assert!(graph.settle(10));
+        assert!(state_of(&graph, q_bar), "Nor of the remaining low input should be high");
+    }
+
+    #[test]
+    fn test_put_node_returns_placed_node() {
+        let mut graph = Graph::new();
+        // Enough nodes for the tree to restructure, placed out of order
+        for i in 0..50 {
+            let position = Vector2i::new(i * 7 % 23, i % 5);
+            let node = graph.put_node(Gate::Or, position);
+            assert_eq!(graph.nodes[node].position, position);
+        }
+
+        let source   = graph.put_node(Gate::Always, Vector2i::new(-1, 0));
+        let replaced = graph.put_node(Gate::Or,     Vector2i::new(-2, 0));
+        graph.wire(source, replaced).unwrap();
+        let replacement = graph.put_node(Gate::Not, Vector2i::new(-2, 0));
+        assert_ne!(replaced, replacement);
+        assert_eq!(graph.find_node_at(Vector2i::new(-2, 0)), Some(replacement));
+        assert!(graph.nodes[source].outputs.is_empty(), "replaced node's wires should be removed with it");
+    }
+
+    #[test]
+    fn test_move_node() {
+        let mut graph = Graph::new();
+        let source = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let sink   = graph.put_node(Gate::Or,     Vector2i::new(1, 0));
+        graph.wire(source, sink).unwrap();
+
+        assert!(graph.move_node(sink, Vector2i::new(5, 5)));
+        assert_eq!(graph.nodes[sink].position(), Vector2i::new(5, 5));
+        assert!(graph.find_node_at(Vector2i::new(1, 0)).is_none());
+        assert_eq!(graph.find_node_at(Vector2i::new(5, 5)), Some(sink));
+        graph.settle(10);
+        assert!(state_of(&graph, sink), "wires should survive the move");
+
+        // The wire left `source` heading right, then turned down into `sink`
+        let bend = graph.put_node(Gate::Or, Vector2i::new(4, 2));
+        graph.wire_along(source, bend, Vec::from([Vector2i::new(4, 0)])).unwrap();
+        assert!(graph.move_node(bend, Vector2i::new(6, 3)));
+        assert_eq!(graph.nodes[bend].inputs[0].elbows, [Vector2i::new(6, 0)], "the bend should follow the moved end");
+        assert!(graph.move_node(source, Vector2i::new(0, -2)));
+        assert_eq!(graph.nodes[bend].inputs[0].elbows, [Vector2i::new(6, -2)]);
+        let points = graph.wire_points(&graph.nodes[bend].inputs[0], &graph.nodes[bend]).unwrap();
+        assert_eq!(points, [Vector2i::new(0, -2), Vector2i::new(6, -2), Vector2i::new(6, 3)].map(|p| graph.grid_to_world_centered(p)));
+        assert!(graph.move_node(source, Vector2i::new(0, 0)));
+
+        assert!(!graph.move_node(sink, Vector2i::new(0, 0)), "cell is taken");
+        assert_eq!(graph.nodes[sink].position(), Vector2i::new(5, 5));
+
+        let removed = graph.put_node(Gate::Or, Vector2i::new(7, 7));
+        graph.remove_node(removed);
+        assert!(!graph.move_node(removed, Vector2i::new(9, 9)), "node is no longer in the graph");
+        assert!(graph.find_node_at(Vector2i::new(9, 9)).is_none());
+    }
+
+    #[test]
+    fn test_edit_elbows() {
+        let mut graph = Graph::new();
+        let source = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let sink   = graph.put_node(Gate::Or,     Vector2i::new(4, 4));
+        let route = Vec::from([Vector2i::new(0, 2), Vector2i::new(4, 2)]);
+        graph.wire_along(source, sink, route.clone()).unwrap();
+        assert_eq!(graph.nodes[sink].inputs[0].elbows, route);
+
+        let elbow = graph.find_elbow_at(Vector2i::new(4, 2)).expect("should find the second elbow");
+        assert_eq!(elbow.index, 1);
+        assert!(graph.find_elbow_at(Vector2i::new(1, 1)).is_none());
+
+        assert!(graph.move_elbow(&elbow, Vector2i::new(4, 3)));
+        assert_eq!(graph.nodes[sink].inputs[0].elbows, [Vector2i::new(0, 2), Vector2i::new(4, 3)]);
+
+        let first = graph.find_elbow_at(Vector2i::new(0, 2)).unwrap();
+        assert!(graph.remove_elbow(&first));
+        assert_eq!(graph.nodes[sink].inputs[0].elbows, [Vector2i::new(4, 3)]);
+        assert!(!graph.move_elbow(&elbow, Vector2i::new(9, 9)), "index is past the end once an earlier elbow is removed");
+
+        graph.remove_wire(source, sink);
+        assert!(!graph.remove_elbow(&first), "wire is gone");
+    }
+
+    #[test]
+    fn test_reroute_wires_avoids_nodes() {
+        let mut graph = Graph::new();
+        let source  = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let sink    = graph.put_node(Gate::Or,     Vector2i::new(4, 0));
+        let blocker = graph.put_node(Gate::Or,     Vector2i::new(2, 0));
+        graph.wire(source, sink).unwrap();
+        graph.wire(source, blocker).unwrap();
+
+        graph.reroute_wires([source]);
+        let elbows = graph.nodes[sink].inputs[0].elbows.clone();
+        assert_eq!(elbows.len(), 2, "going around the blocker takes two bends: {elbows:?}");
+        assert!(elbows.iter().all(|elbow| elbow.y != 0), "wire should leave the row the blocker is in");
+        assert!(graph.nodes[blocker].inputs[0].elbows.is_empty(), "nothing is in the way of the blocker's own wire");
+    }
+
+    #[test]
+    fn test_find_wire_at() {
+        let mut graph = Graph::new();
+        let source = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let sink   = graph.put_node(Gate::Or,     Vector2i::new(4, 4));
+        let other  = graph.put_node(Gate::Or,     Vector2i::new(0, 4));
+        graph.wire_along(source, sink, Vec::from([Vector2i::new(4, 0)])).unwrap();
+        graph.wire_along(source, other, Vec::new()).unwrap();
+
+        // Halfway down the second segment of the first wire, slightly off to the side
+        let near_elbow_leg = graph.grid_to_world_centered(Vector2i::new(4, 2)) + Vector2::new(2.0, 0.0);
+        let found = graph.find_wire_at(near_elbow_leg, 3.0).expect("should hit the segment after the elbow");
+        assert_eq!(found, WireHandle { input: source, output_port: 0, output: sink, input_port: 0 });
+        assert!(graph.find_wire_at(near_elbow_leg, 1.0).is_none(), "point is outside the tolerance");
+
+        let on_straight_wire = graph.grid_to_world_centered(Vector2i::new(0, 3));
+        let found = graph.find_wire_at(on_straight_wire, 1.0).unwrap();
+        assert_eq!(found.output, other);
+
+        assert!(graph.find_wire_at(graph.grid_to_world_centered(Vector2i::new(2, 2)), 3.0).is_none());
+
+        assert!(graph.reroute_wire(&found));
+        graph.remove_wire(source, other);
+        assert!(!graph.reroute_wire(&found), "wire is gone");
+    }
+
+    #[test]
+    fn test_move_nodes() {
+        let mut graph = Graph::new();
+        let a       = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let b       = graph.put_node(Gate::Or,     Vector2i::new(1, 0));
+        let outside = graph.put_node(Gate::Or,     Vector2i::new(0, 5));
+        graph.wire_along(a, b, Vec::from([Vector2i::new(0, -1), Vector2i::new(1, -1)])).unwrap();
+        graph.wire_along(b, outside, Vec::from([Vector2i::new(1, 5)])).unwrap();
+
+        // Shifting right by one lands `a` where `b` was, which is fine since `b` moves too
+        assert!(graph.move_nodes(&[a, b], Vector2i::new(1, 0)));
+        assert_eq!(graph.nodes[a].position(), Vector2i::new(1, 0));
+        assert_eq!(graph.nodes[b].position(), Vector2i::new(2, 0));
+        assert_eq!(graph.find_node_at(Vector2i::new(1, 0)), Some(a));
+        assert!(graph.find_node_at(Vector2i::new(0, 0)).is_none());
+        assert_eq!(graph.nodes[b].inputs[0].elbows, [Vector2i::new(1, -1), Vector2i::new(2, -1)], "internal wires move along");
+        assert_eq!(graph.nodes[outside].inputs[0].elbows, [Vector2i::new(2, 5)], "the bend next to a moved end follows it");
+
+        assert!(!graph.move_nodes(&[a, b], Vector2i::new(-1, 5)), "`a` would land on a node outside the group");
+        assert_eq!(graph.nodes[a].position(), Vector2i::new(1, 0));
+        assert_eq!(graph.nodes[b].position(), Vector2i::new(2, 0));
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut graph = Graph::new();
+        let a = graph.put_node(Gate::Never, Vector2i::new(0, 0));
+        let b = graph.put_node(Gate::Never, Vector2i::new(1, 0));
+        assert!(graph.set_label(b, Some(String::from("b"))));
+        assert!(graph.set_label(a, Some(String::from("a"))));
+        assert!(graph.set_label(a, Some(String::from("a"))), "relabeling with the same name is fine");
+        assert!(!graph.set_label(b, Some(String::from("a"))), "labels should be unique");
+        assert!(!graph.set_label(b, Some(String::from("two words"))));
+        assert!(!graph.set_label(b, Some(String::new())));
+        assert_eq!(graph.nodes[b].label(), Some("b"));
+
+        assert_eq!(graph.find_node_by_label("a"), Some(a));
+        let labels: Vec<_> = graph.labeled_nodes().iter().map(|&id| graph.nodes[id].label.clone().unwrap()).collect();
+        assert_eq!(labels, ["a", "b"]);
+
+        assert!(graph.set_label(a, None));
+        assert!(graph.find_node_by_label("a").is_none());
+    }
+
+    #[test]
+    fn test_drive_inputs() {
+        let mut graph = Graph::new();
+        let toggle = graph.put_node(Gate::Toggle(false), Vector2i::new(0, 0));
+        let button = graph.put_node(Gate::Button(false), Vector2i::new(0, 1));
+        let and    = graph.put_node(Gate::And,           Vector2i::new(1, 0));
+        graph.wire(toggle, and).unwrap();
+        graph.wire(button, and).unwrap();
+        graph.settle(10);
+        assert!(!state_of(&graph, and));
+
+        assert!(graph.drive(toggle, true));
+        assert!(graph.drive(button, true));
+        graph.settle(10);
+        assert_eq!(graph.nodes[toggle].gate(), Gate::Toggle(true), "driving should keep the kind of input");
+        assert!(state_of(&graph, and));
+
+        assert!(graph.drive(button, false));
+        graph.settle(10);
+        assert!(!state_of(&graph, and));
+
+        assert!(!graph.drive(and, true), "computed gates can't be driven");
+        assert_eq!(graph.nodes[and].gate(), Gate::And);
+    }
+
+    #[test]
+    fn test_graph_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Graph>();
+    }
 }
@@ -0,0 +1,142 @@
+use std::ops::{Index, IndexMut};
+
+/// Refers to a node in a [`Graph`](super::Graph).
+///
+/// IDs stay valid for as long as the node exists. Slots are reused once a node is removed, but with a new
+/// generation, so an old ID never refers to whatever takes its place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Storage that hands out a [`NodeId`] for each value
+#[derive(Debug, Clone)]
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    /// Indices of empty slots
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Self {
+        Self { slots: Vec::new(), free: Vec::new(), len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) -> NodeId {
+        self.len += 1;
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.value = Some(value);
+            return NodeId { index, generation: slot.generation };
+        }
+        let index = u32::try_from(self.slots.len()).expect("arena is full");
+        self.slots.push(Slot { generation: 0, value: Some(value) });
+        NodeId { index, generation: 0 }
+    }
+
+    /// Returns `None` if the ID is stale.
+    pub fn remove(&mut self, id: NodeId) -> Option<T> {
+        let slot = self.slots.get_mut(id.index as usize).filter(|slot| slot.generation == id.generation)?;
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    /// Every value with its ID, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let value = slot.value.as_ref()?;
+            Some((NodeId { index: index as u32, generation: slot.generation }, value))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (NodeId, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(index, slot)| {
+            let value = slot.value.as_mut()?;
+            Some((NodeId { index: index as u32, generation: slot.generation }, value))
+        })
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.iter().map(|(id, _)| id)
+    }
+}
+
+/// Panics if the ID is stale
+impl<T> Index<NodeId> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: NodeId) -> &T {
+        self.get(id).expect("stale node id")
+    }
+}
+
+impl<T> IndexMut<NodeId> for Arena<T> {
+    fn index_mut(&mut self, id: NodeId) -> &mut T {
+        self.get_mut(id).expect("stale node id")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_ids() {
+        let mut arena = Arena::new();
+        let a = arena.insert('a');
+        let b = arena.insert('b');
+        assert_eq!(arena.remove(a), Some('a'));
+        assert_eq!(arena.remove(a), None);
+
+        let c = arena.insert('c');
+        assert_ne!(a, c, "reused slot gets a new generation");
+        assert_eq!(arena.get(a), None);
+        assert_eq!(arena[c], 'c');
+        assert_eq!(arena.len(), 2);
+        assert_eq!(arena.iter().map(|(_, &value)| value).collect::<String>(), "cb");
+        assert!(arena.contains(b));
+    }
+}
@@ -0,0 +1,280 @@
+//! Subcircuits saved under a name and placed as a single node.
+//!
+//! A component's pins are the labeled nodes of its circuit. Labeled input gates (see [`Gate::is_input`]) are driven
+//! by the wires into the component, and every other labeled node is one of its outputs. Both kinds of pin are
+//! ordered top to bottom, then left to right, the way they would be read off a schematic.
+
+use std::{error::Error, fmt};
+use super::{node::{gate::Gate, Node}, Graph, NodeId};
+
+/// Refers to one of the components a [`Graph`] knows about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ComponentId(pub(in crate::graph) u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComponentError {
+    /// Names are single words that can't be mistaken for a gate and can be used as a file name
+    InvalidName(String),
+    /// None of the circuit's labeled nodes can be read from outside
+    NoOutputs,
+}
+
+impl fmt::Display for ComponentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "`{name}` can't name a component; use a single word without dots or slashes that isn't a gate name"),
+            Self::NoOutputs => write!(f, "a component needs at least one labeled node that isn't an input to use as an output pin"),
+        }
+    }
+}
+
+impl Error for ComponentError {}
+
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub(in crate::graph) name: String,
+    pub(in crate::graph) circuit: Graph,
+    /// Nodes of the circuit driven by the component's inputs, in pin order
+    pub(in crate::graph) inputs: Vec<NodeId>,
+    /// Nodes of the circuit read by the component's outputs, in pin order
+    pub(in crate::graph) outputs: Vec<NodeId>,
+}
+
+impl Component {
+    pub fn new(name: impl Into<String>, circuit: Graph) -> Result<Self, ComponentError> {
+        let name = name.into();
+        if !Self::is_valid_name(&name) {
+            return Err(ComponentError::InvalidName(name));
+        }
+
+        let mut pins = circuit.labeled_nodes();
+        pins.sort_by_key(|&id| {
+            let position = circuit.nodes[id].position;
+            (position.y, position.x)
+        });
+        let (inputs, outputs): (Vec<_>, Vec<_>) = pins.into_iter().partition(|&id| circuit.nodes[id].gate.is_input());
+        if outputs.is_empty() {
+            return Err(ComponentError::NoOutputs);
+        }
+        Ok(Self { name, circuit, inputs, outputs })
+    }
+
+    /// A component made of copies of the given nodes and the wires between them
+    pub fn from_nodes(graph: &Graph, name: impl Into<String>, nodes: impl IntoIterator<Item = NodeId>) -> Result<Self, ComponentError> {
+        let mut text = Vec::new();
+        graph.save_nodes(nodes, &mut text).expect("writing to memory cannot fail");
+        let circuit = Graph::load(text.as_slice()).expect("saved nodes should load back");
+        Self::new(name, circuit)
+    }
+
+    /// Component names share a namespace with gates in saved circuits, and name the files components are kept in.
+    pub fn is_valid_name(name: &str) -> bool {
+        Node::is_valid_label(name)
+            && !name.contains(['.', '/', '\\'])
+            && name.parse::<Gate>().is_err()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The circuit as it was saved, before any copy of it was placed
+    pub fn circuit(&self) -> &Graph {
+        &self.circuit
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Label of the `index`th input pin
+    pub fn input_name(&self, index: usize) -> Option<&str> {
+        self.inputs.get(index).and_then(|&id| self.circuit.nodes[id].label())
+    }
+
+    /// Label of the `index`th output pin
+    pub fn output_name(&self, index: usize) -> Option<&str> {
+        self.outputs.get(index).and_then(|&id| self.circuit.nodes[id].label())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{graph::{signal::Signal, wire::WireError}, vector2i::Vector2i};
+    use super::*;
+
+    /// Sum and carry of two bits; the inputs are labeled out of pin order to check that pins are sorted by position
+    fn half_adder() -> Component {
+        let mut circuit = Graph::new();
+        let b     = circuit.put_node(Gate::Never, Vector2i::new(0, 2));
+        let a     = circuit.put_node(Gate::Never, Vector2i::new(0, 0));
+        let sum   = circuit.put_node(Gate::Xor,   Vector2i::new(2, 0));
+        let carry = circuit.put_node(Gate::And,   Vector2i::new(2, 2));
+        for (node, label) in [(a, "a"), (b, "b"), (sum, "sum"), (carry, "carry")] {
+            circuit.set_label(node, Some(String::from(label)));
+        }
+        for output in [sum, carry] {
+            circuit.wire(a, output).unwrap();
+            circuit.wire(b, output).unwrap();
+        }
+        Component::new("half-adder", circuit).unwrap()
+    }
+
+    #[test]
+    fn test_pins() {
+        let adder = half_adder();
+        assert_eq!((adder.input_count(), adder.output_count()), (2, 2));
+        assert_eq!([adder.input_name(0), adder.input_name(1)], [Some("a"), Some("b")]);
+        assert_eq!([adder.output_name(0), adder.output_name(1)], [Some("sum"), Some("carry")]);
+        assert_eq!(adder.output_name(2), None);
+
+        assert_eq!(Component::new("xor", Graph::new()).err(), Some(ComponentError::InvalidName(String::from("xor"))));
+        assert_eq!(Component::new("two words", Graph::new()).err(), Some(ComponentError::InvalidName(String::from("two words"))));
+        for name in ["adder.v2", "..", "../adder", "lib/adder", "lib\\adder"] {
+            assert_eq!(Component::new(name, Graph::new()).err(), Some(ComponentError::InvalidName(String::from(name))), "{name} isn't a file name");
+        }
+        assert_eq!(Component::new("empty", Graph::new()).err(), Some(ComponentError::NoOutputs));
+    }
+
+    #[test]
+    fn test_component_node() {
+        let mut graph = Graph::new();
+        let adder = graph.add_component(half_adder());
+        assert_eq!(graph.add_component(half_adder()), adder, "components are shared by name");
+
+        let a   = graph.put_node(Gate::Toggle(false), Vector2i::new(0, 0));
+        let b   = graph.put_node(Gate::Toggle(false), Vector2i::new(0, 1));
+        let add = graph.put_node(Gate::Component(adder), Vector2i::new(2, 0));
+        let sum   = graph.put_node(Gate::Or, Vector2i::new(4, 0));
+        let carry = graph.put_node(Gate::Or, Vector2i::new(4, 1));
+        // Wired out of order; pins go by port
+        graph.wire_ports_along(b, 0, add, 1, Vec::new()).unwrap();
+        assert_eq!(graph.can_wire_ports(a, 0, add, 1), Err(WireError::PortTaken));
+        assert_eq!(graph.can_wire_ports(a, 0, add, 2), Err(WireError::NoSuchInput { inputs: 2 }));
+        graph.wire(a, add).unwrap();
+        assert_eq!(graph.wire(sum, add), Err(WireError::InputLimitExceeded { gate: Gate::Component(adder), max: 2 }));
+        graph.wire_ports_along(add, 0, sum, 0, Vec::new()).unwrap();
+        graph.wire_ports_along(add, 1, carry, 0, Vec::new()).unwrap();
+        let unwired = graph.put_node(Gate::Or, Vector2i::new(4, 2));
+        assert_eq!(graph.can_wire_ports(add, 2, unwired, 0), Err(WireError::NoSuchOutput { outputs: 2 }));
+
+        assert_eq!(graph.input_port_name(Gate::Component(adder), 1), Some("b"));
+        assert_eq!(graph.output_port_name(Gate::Component(adder), 1), Some("carry"));
+
+        for (a_on, b_on) in [(false, false), (true, false), (false, true), (true, true)] {
+            graph.drive(a, a_on);
+            graph.drive(b, b_on);
+            assert!(graph.settle(10));
+            assert_eq!(graph.output_state(add, 0), a_on ^ b_on);
+            assert_eq!(graph.nodes[sum].state(), a_on ^ b_on, "pins should drive the nodes wired to them");
+            assert_eq!(graph.nodes[carry].state(), a_on && b_on);
+        }
+
+        assert_eq!(graph.set_gate(add, Gate::Or), Err(WireError::NoSuchOutput { outputs: 1 }), "carry reads a pin an Or doesn't have");
+        graph.remove_wire(add, carry);
+        graph.set_gate(add, Gate::Or).unwrap();
+        assert!(graph.nodes[add].circuit.is_none());
+    }
+
+    #[test]
+    fn test_update_component() {
+        let mut graph = Graph::new();
+        let adder = graph.add_component(half_adder());
+        let a   = graph.put_node(Gate::Toggle(true), Vector2i::new(0, 0));
+        let add = graph.put_node(Gate::Component(adder), Vector2i::new(2, 0));
+        graph.wire(a, add).unwrap();
+        assert!(graph.settle(10));
+        assert!(graph.output_state(add, 0));
+        assert_eq!(graph.update_component(half_adder()), Ok(adder), "an unchanged component is left alone");
+
+        // Saved again with the sum made a Nor
+        let mut circuit = half_adder().circuit;
+        let sum = circuit.find_node_by_label("sum").unwrap();
+        circuit.set_gate(sum, Gate::Nor).unwrap();
+        assert_eq!(graph.update_component(Component::new("half-adder", circuit).unwrap()), Ok(adder));
+        assert!(graph.settle(10));
+        assert!(!graph.output_state(add, 0), "placed copies should run the new circuit");
+
+        // Saved again with a single input, which the placed copy has too many wires for
+        let mut circuit = half_adder().circuit;
+        circuit.remove_node(circuit.find_node_by_label("b").unwrap());
+        let b = graph.put_node(Gate::Toggle(false), Vector2i::new(0, 1));
+        graph.wire(b, add).unwrap();
+        let narrower = Component::new("half-adder", circuit).unwrap();
+        assert_eq!(graph.update_component(narrower), Err(WireError::InputLimitExceeded { gate: Gate::Component(adder), max: 1 }));
+        assert_eq!(graph.component(adder).unwrap().input_count(), 2, "the old copy is kept when the new one doesn't fit");
+    }
+
+    #[test]
+    fn test_buses_pass_through_pins() {
+        // Swaps the two halves of a byte
+        let mut swap = Graph::new();
+        let input  = swap.put_node(Gate::Never,    Vector2i::new(0, 0));
+        let split  = swap.put_node(Gate::Split(8), Vector2i::new(1, 0));
+        let join   = swap.put_node(Gate::Join(8),  Vector2i::new(2, 0));
+        swap.set_label(input, Some(String::from("in")));
+        swap.set_label(join, Some(String::from("out")));
+        swap.wire(input, split).unwrap();
+        for bit in 0..8 {
+            swap.wire_ports_along(split, bit, join, (bit + 4) % 8, Vec::new()).unwrap();
+        }
+
+        let mut graph = Graph::new();
+        let swap = graph.add_component(Component::new("swap", swap).unwrap());
+        let bits: Vec<_> = (0..8).map(|y| graph.put_node(Gate::Toggle(false), Vector2i::new(0, y))).collect();
+        let join = graph.put_node(Gate::Join(8), Vector2i::new(2, 0));
+        let node = graph.put_node(Gate::Component(swap), Vector2i::new(4, 0));
+        for &bit in bits.iter() {
+            graph.wire(bit, join).unwrap();
+        }
+        graph.wire(join, node).unwrap();
+        for (&bit, on) in bits.iter().zip([true, false, true, true, false, false, false, true]) {
+            graph.drive(bit, on);
+        }
+        assert!(graph.settle(20));
+        assert_eq!(graph.nodes[join].value(), Signal::new(0x8d, 8));
+        assert_eq!(graph.output_value(node, 0), Signal::new(0xd8, 8));
+    }
+
+    #[test]
+    fn test_instances_hold_their_own_state() {
+        // A Nor latch, set and reset from outside
+        let mut latch = Graph::new();
+        let set   = latch.put_node(Gate::Never, Vector2i::new(0, 0));
+        let reset = latch.put_node(Gate::Never, Vector2i::new(0, 2));
+        let q_bar = latch.put_node(Gate::Nor,   Vector2i::new(2, 0));
+        let q     = latch.put_node(Gate::Nor,   Vector2i::new(2, 2));
+        latch.wire(set, q_bar).unwrap();
+        latch.wire(reset, q).unwrap();
+        latch.wire(q, q_bar).unwrap();
+        latch.wire(q_bar, q).unwrap();
+        for (node, label) in [(set, "set"), (reset, "reset"), (q, "q")] {
+            latch.set_label(node, Some(String::from(label)));
+        }
+
+        let mut graph = Graph::new();
+        let latch = graph.add_component(Component::new("latch", latch).unwrap());
+        let set    = graph.put_node(Gate::Button(false), Vector2i::new(0, 0));
+        let low    = graph.put_node(Gate::Never,         Vector2i::new(0, 1));
+        let high   = graph.put_node(Gate::Always,        Vector2i::new(0, 2));
+        let first  = graph.put_node(Gate::Component(latch), Vector2i::new(2, 0));
+        let second = graph.put_node(Gate::Component(latch), Vector2i::new(2, 4));
+        graph.wire(set, first).unwrap();
+        graph.wire(low, first).unwrap();
+        // The second copy is held in reset
+        graph.wire(low, second).unwrap();
+        graph.wire(high, second).unwrap();
+        assert!(graph.settle(20));
+
+        graph.drive(set, true);
+        assert!(graph.settle(20));
+        graph.drive(set, false);
+        assert!(graph.settle(20));
+        assert!(graph.output_state(first, 0), "latch should remember being set");
+        assert!(!graph.output_state(second, 0), "the other copy of the latch was never set");
+    }
+}
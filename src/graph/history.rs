@@ -0,0 +1,871 @@
+//! Undoable edits.
+//!
+//! Every edit is recorded as a [`Command`] that knows how to reverse itself. Commands name nodes by the cell they
+//! sit in rather than by ID, since undoing a removal brings the node back under a new ID. Replaying commands
+//! in order, or their inverses in reverse order, keeps those cells pointing at the right nodes.
+
+use std::{collections::VecDeque, error::Error, fmt, mem::size_of};
+use crate::vector2i::Vector2i;
+use super::{node::{gate::Gate, Node}, same_circuit, wire::{ElbowHandle, WireError, WireHandle}, Graph, NodeId};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasteError {
+    /// A pasted node would land on the node already in this cell
+    Overlap(Vector2i),
+    /// The snippet places a component whose circuit differs from the graph's component of the same name
+    ComponentMismatch(String),
+}
+
+impl fmt::Display for PasteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overlap(Vector2i { x, y }) => write!(f, "another node is already at {x} {y}"),
+            Self::ComponentMismatch(name) => write!(f, "the circuit already has a different component named {name}"),
+        }
+    }
+}
+
+impl Error for PasteError {}
+
+/// Everything needed to put a removed node back the way it was
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeSnapshot {
+    gate: Gate,
+    position: Vector2i,
+    label: Option<String>,
+    /// Input positions, the ports connected and elbows of the wires into the node, in order
+    inputs: Vec<(Vector2i, usize, usize, Vec<Vector2i>)>,
+    /// Output positions, the ports connected, where the wire sits among the output's inputs,
+    /// and elbows of the wires out of the node
+    outputs: Vec<(Vector2i, usize, usize, usize, Vec<Vector2i>)>,
+}
+
+impl NodeSnapshot {
+    fn of(graph: &Graph, id: NodeId) -> Option<Self> {
+        let node = graph.node(id)?;
+        let inputs = node.inputs
+            .iter()
+            .filter_map(|wire| Some((graph.node(wire.input)?.position, wire.output_port, wire.input_port, wire.elbows.clone())))
+            .collect();
+        // A node is listed once per wire into it, and there can be several between the same two nodes
+        let mut output_ids = node.outputs.clone();
+        output_ids.sort();
+        output_ids.dedup();
+        let outputs = output_ids
+            .into_iter()
+            .filter_map(|output| graph.node(output))
+            .flat_map(|output| {
+                output.inputs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, wire)| wire.input == id)
+                    .map(|(index, wire)| (output.position, wire.output_port, wire.input_port, index, wire.elbows.clone()))
+            })
+            .collect();
+        Some(Self { gate: node.gate, position: node.position, label: node.label.clone(), inputs, outputs })
+    }
+
+    /// A node with no label or wires
+    fn new(gate: Gate, position: Vector2i) -> Self {
+        Self { gate, position, label: None, inputs: Vec::new(), outputs: Vec::new() }
+    }
+
+    fn restore(&self, graph: &mut Graph) -> bool {
+        if graph.find_node_at(self.position).is_some() {
+            return false;
+        }
+        let mut node = Node::new(self.gate, self.position);
+        node.label = self.label.clone();
+        let id = graph.add_node(node);
+        for (position, output_port, input_port, elbows) in self.inputs.iter() {
+            if let Some(input) = graph.find_node_at(*position) {
+                graph.connect(input, *output_port, id, *input_port, elbows.clone());
+            }
+        }
+        for (position, output_port, input_port, index, elbows) in self.outputs.iter() {
+            if let Some(output) = graph.find_node_at(*position) {
+                let wire = WireHandle { input: id, output_port: *output_port, output, input_port: *input_port };
+                reconnect(graph, &wire, *index, elbows.clone());
+            }
+        }
+        true
+    }
+
+    /// Rough number of bytes held outside the struct itself
+    fn heap_size(&self) -> usize {
+        self.label.as_ref().map_or(0, String::len)
+            + self.inputs.iter().map(|(.., elbows)| size_of::<(Vector2i, usize, usize, Vec<Vector2i>)>() + elbows.len() * size_of::<Vector2i>()).sum::<usize>()
+            + self.outputs.iter().map(|(.., elbows)| size_of::<(Vector2i, usize, usize, usize, Vec<Vector2i>)>() + elbows.len() * size_of::<Vector2i>()).sum::<usize>()
+    }
+}
+
+/// A single reversible edit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    AddNode(NodeSnapshot),
+    RemoveNode(NodeSnapshot),
+    /// Creates the wire, placing it at `index` among the output's inputs
+    Wire { wire: WireAt, index: usize, elbows: Vec<Vector2i> },
+    Unwire { wire: WireAt, index: usize, elbows: Vec<Vector2i> },
+    /// Moves the nodes at `positions` by `offset`, as [`Graph::move_nodes`] does.
+    /// The elbows of wires leading out of the moved nodes are recorded rather than recomputed, so moving back restores them.
+    MoveNodes { positions: Vec<Vector2i>, offset: Vector2i, wires: Vec<MovedWire> },
+    /// Changes the gate along with the input port of each wire into the node, in order
+    SetGate { position: Vector2i, from: Gate, to: Gate, from_ports: Vec<usize>, to_ports: Vec<usize> },
+    SetLabel { position: Vector2i, from: Option<String>, to: Option<String> },
+    SetElbows { wire: WireAt, from: Vec<Vector2i>, to: Vec<Vector2i> },
+}
+
+/// A wire with one end among moving nodes, named by where its ends were before the move, with its elbows before and after
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovedWire {
+    wire: WireAt,
+    from: Vec<Vector2i>,
+    to: Vec<Vector2i>,
+}
+
+/// A wire named by the cells of the nodes at either end, along with the ports it connects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireAt {
+    input: Vector2i,
+    output_port: usize,
+    output: Vector2i,
+    input_port: usize,
+}
+
+impl WireAt {
+    /// The same wire once the nodes at `moved` are shifted by `offset`
+    fn shifted(self, moved: &[Vector2i], offset: Vector2i) -> Self {
+        let shift = |position: Vector2i| if moved.contains(&position) { position + offset } else { position };
+        Self { input: shift(self.input), output: shift(self.output), ..self }
+    }
+
+    fn of(graph: &Graph, wire: &WireHandle) -> Option<Self> {
+        let (input, output) = (graph.node(wire.input)?, graph.node(wire.output)?);
+        Some(Self { input: input.position, output_port: wire.output_port, output: output.position, input_port: wire.input_port })
+    }
+
+    fn find(&self, graph: &Graph) -> Option<WireHandle> {
+        let (input, output) = (graph.find_node_at(self.input)?, graph.find_node_at(self.output)?);
+        Some(WireHandle { input, output_port: self.output_port, output, input_port: self.input_port })
+    }
+}
+
+impl Command {
+    pub fn inverse(&self) -> Self {
+        match self.clone() {
+            Self::AddNode(snapshot) => Self::RemoveNode(snapshot),
+            Self::RemoveNode(snapshot) => Self::AddNode(snapshot),
+            Self::Wire { wire, index, elbows } => Self::Unwire { wire, index, elbows },
+            Self::Unwire { wire, index, elbows } => Self::Wire { wire, index, elbows },
+            Self::MoveNodes { positions, offset, wires } => Self::MoveNodes {
+                wires: wires
+                    .into_iter()
+                    .map(|moved| MovedWire { wire: moved.wire.shifted(&positions, offset), from: moved.to, to: moved.from })
+                    .collect(),
+                positions: positions.into_iter().map(|position| position + offset).collect(),
+                offset: Vector2i::default() - offset,
+            },
+            Self::SetGate { position, from, to, from_ports, to_ports } => Self::SetGate { position, from: to, to: from, from_ports: to_ports, to_ports: from_ports },
+            Self::SetLabel { position, from, to } => Self::SetLabel { position, from: to, to: from },
+            Self::SetElbows { wire, from, to } => Self::SetElbows { wire, from: to, to: from },
+        }
+    }
+
+    /// Returns `false` without changing anything if the graph doesn't look the way the command expects.
+    pub fn apply(&self, graph: &mut Graph) -> bool {
+        match self {
+            Self::AddNode(snapshot) => snapshot.restore(graph),
+            Self::RemoveNode(snapshot) => graph.find_node_at(snapshot.position).is_some_and(|id| graph.remove_node(id)),
+            Self::Wire { wire, index, elbows } => match wire.find(graph) {
+                Some(wire) if graph.can_wire_ports(wire.input, wire.output_port, wire.output, wire.input_port).is_ok() => {
+                    reconnect(graph, &wire, *index, elbows.clone());
+                    true
+                },
+                _ => false,
+            },
+            Self::Unwire { wire, .. } => wire.find(graph).is_some_and(|wire| graph.unwire(&wire)),
+            Self::MoveNodes { positions, offset, wires } => {
+                let ids = positions.iter().map(|&position| graph.find_node_at(position)).collect::<Option<Vec<_>>>();
+                let handles = wires.iter().map(|moved| moved.wire.find(graph)).collect::<Option<Vec<_>>>();
+                let (Some(ids), Some(handles)) = (ids, handles) else {
+                    return false;
+                };
+                if !graph.move_nodes(&ids, *offset) {
+                    return false;
+                }
+                for (handle, moved) in handles.iter().zip(wires) {
+                    graph.with_wire(handle, |wire| wire.elbows = moved.to.clone());
+                }
+                true
+            },
+            Self::SetGate { position, to, to_ports, .. } => graph.find_node_at(*position).is_some_and(|id| {
+                let fits = graph.can_set_gate(id, *to).is_ok() && graph.nodes[id].inputs.len() == to_ports.len();
+                if fits {
+                    graph.set_gate_with_ports(id, *to, to_ports);
+                }
+                fits
+            }),
+            Self::SetLabel { position, to, .. } => graph.find_node_at(*position).is_some_and(|id| graph.set_label(id, to.clone())),
+            Self::SetElbows { wire, to, .. } => wire.find(graph).is_some_and(|wire| graph.with_wire(&wire, |wire| wire.elbows = to.clone()).is_some()),
+        }
+    }
+
+    /// Rough number of bytes the command takes up in the history
+    fn size(&self) -> usize {
+        let elbows = |elbows: &Vec<Vector2i>| elbows.len() * size_of::<Vector2i>();
+        size_of::<Self>() + match self {
+            Self::AddNode(snapshot) | Self::RemoveNode(snapshot) => snapshot.heap_size(),
+            Self::Wire { elbows: cells, .. } | Self::Unwire { elbows: cells, .. } => elbows(cells),
+            Self::MoveNodes { positions, wires, .. } => {
+                elbows(positions) + wires.iter().map(|moved| size_of::<MovedWire>() + elbows(&moved.from) + elbows(&moved.to)).sum::<usize>()
+            },
+            Self::SetGate { from_ports, to_ports, .. } => (from_ports.len() + to_ports.len()) * size_of::<usize>(),
+            Self::SetLabel { from, to, .. } => from.as_ref().map_or(0, String::len) + to.as_ref().map_or(0, String::len),
+            Self::SetElbows { from, to, .. } => elbows(from) + elbows(to),
+        }
+    }
+
+    /// Folds `next` into this command if together they amount to one, like the steps of a drag.
+    fn merge(&mut self, next: &Command) -> bool {
+        match (self, next) {
+            (
+                Self::MoveNodes { positions, offset, wires },
+                Self::MoveNodes { positions: next_positions, offset: next_offset, wires: next_wires },
+            )
+                if positions.len() == next_positions.len()
+                && positions.iter().zip(next_positions).all(|(&position, &next)| position + *offset == next) =>
+            {
+                for next in next_wires {
+                    // Named by where its ends were before either move
+                    let wire = next.wire.shifted(next_positions, Vector2i::default() - *offset);
+                    match wires.iter_mut().find(|moved| moved.wire == wire) {
+                        Some(moved) => moved.to = next.to.clone(),
+                        None => wires.push(MovedWire { wire, from: next.from.clone(), to: next.to.clone() }),
+                    }
+                }
+                *offset = *offset + *next_offset;
+                true
+            },
+            (Self::SetElbows { wire, to, .. }, Self::SetElbows { wire: next_wire, from: next_from, to: next_to })
+                if wire == next_wire && to == next_from =>
+            {
+                *to = next_to.clone();
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Applies the commands in order. If one fails, the ones before it are reverted and `false` is returned.
+fn apply_all(graph: &mut Graph, commands: &[Command]) -> bool {
+    for (index, command) in commands.iter().enumerate() {
+        if !command.apply(graph) {
+            for applied in commands[..index].iter().rev() {
+                applied.inverse().apply(graph);
+            }
+            return false;
+        }
+    }
+    true
+}
+
+/// Creates the wire and puts it at `index` among the output's inputs, or last if there are fewer. The wire must be valid.
+fn reconnect(graph: &mut Graph, wire: &WireHandle, index: usize, elbows: Vec<Vector2i>) {
+    graph.connect(wire.input, wire.output_port, wire.output, wire.input_port, elbows);
+    let inputs = &mut graph.nodes[wire.output].inputs;
+    if index < inputs.len() {
+        let wire = inputs.pop().expect("wire was just connected");
+        inputs.insert(index, wire);
+    }
+}
+
+/// Edits that can be undone and redone.
+///
+/// Edits made through the history are grouped between [`History::begin_group`] and [`History::end_group`],
+/// or each form their own group otherwise, and are undone a group at a time. The oldest groups are forgotten
+/// once the history takes up more than its budget, though the most recent one is always kept.
+pub struct History {
+    undo: VecDeque<Vec<Command>>,
+    redo: Vec<Vec<Command>>,
+    /// The group being recorded
+    open: Vec<Command>,
+    /// How many groups have begun and not yet ended
+    depth: usize,
+    /// Rough number of bytes used by the undo and redo stacks
+    size: usize,
+    budget: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    pub const DEFAULT_BUDGET: usize = 1 << 20;
+
+    pub fn new() -> Self {
+        Self::with_budget(Self::DEFAULT_BUDGET)
+    }
+
+    /// A history that holds about `budget` bytes of edits
+    pub fn with_budget(budget: usize) -> Self {
+        Self { undo: VecDeque::new(), redo: Vec::new(), open: Vec::new(), depth: 0, size: 0, budget }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || !self.open.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forgets every edit, such as when a different circuit is loaded
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open.clear();
+        self.depth = 0;
+        self.size = 0;
+    }
+
+    /// Starts recording edits as one group. Groups can nest; the outermost one decides where the group ends.
+    pub fn begin_group(&mut self) {
+        self.depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 {
+            self.close_group();
+        }
+    }
+
+    fn close_group(&mut self) {
+        if self.open.is_empty() {
+            return;
+        }
+        let group = std::mem::take(&mut self.open);
+        self.size += group.iter().map(Command::size).sum::<usize>();
+        self.undo.push_back(group);
+        while self.size > self.budget && self.undo.len() > 1 {
+            let forgotten = self.undo.pop_front().expect("undo stack is not empty");
+            self.size -= forgotten.iter().map(Command::size).sum::<usize>();
+        }
+    }
+
+    /// Applies the command and records it if it worked. Clears anything that could have been redone.
+    pub fn apply(&mut self, graph: &mut Graph, command: Command) -> bool {
+        if !command.apply(graph) {
+            return false;
+        }
+        for group in self.redo.drain(..) {
+            self.size -= group.iter().map(Command::size).sum::<usize>();
+        }
+        if !self.open.last_mut().is_some_and(|last| last.merge(&command)) {
+            self.open.push(command);
+        }
+        if self.depth == 0 {
+            self.close_group();
+        }
+        true
+    }
+
+    /// Reverts the most recent group of edits, ending any group still being recorded.
+    /// Returns `false` if there was nothing to undo, or if the graph no longer matches the group,
+    /// in which case it is left as it was and the group stays where it is.
+    pub fn undo(&mut self, graph: &mut Graph) -> bool {
+        self.depth = 0;
+        self.close_group();
+        let Some(group) = self.undo.back() else {
+            return false;
+        };
+        let inverses: Vec<Command> = group.iter().rev().map(Command::inverse).collect();
+        if !apply_all(graph, &inverses) {
+            return false;
+        }
+        let group = self.undo.pop_back().expect("undo stack is not empty");
+        self.redo.push(group);
+        true
+    }
+
+    /// Reapplies the most recently undone group. Returns `false` if there was nothing to redo,
+    /// or if the graph no longer matches the group, as with [`History::undo`].
+    pub fn redo(&mut self, graph: &mut Graph) -> bool {
+        let Some(group) = self.redo.last() else {
+            return false;
+        };
+        if !apply_all(graph, group) {
+            return false;
+        }
+        let group = self.redo.pop().expect("redo stack is not empty");
+        self.undo.push_back(group);
+        true
+    }
+
+    /// Like [`Graph::put_node`], replacing whatever was in the cell.
+    pub fn put_node(&mut self, graph: &mut Graph, gate: Gate, position: Vector2i) -> NodeId {
+        self.begin_group();
+        if let Some(existing) = graph.find_node_at(position) {
+            self.remove_node(graph, existing);
+        }
+        self.apply(graph, Command::AddNode(NodeSnapshot::new(gate, position)));
+        self.end_group();
+        graph.find_node_at(position).expect("node was just placed")
+    }
+
+    /// Like [`Graph::remove_node`]
+    pub fn remove_node(&mut self, graph: &mut Graph, id: NodeId) -> bool {
+        NodeSnapshot::of(graph, id).is_some_and(|snapshot| self.apply(graph, Command::RemoveNode(snapshot)))
+    }
+
+    /// Like [`Graph::wire_along`]
+    pub fn wire_along(&mut self, graph: &mut Graph, input: NodeId, output: NodeId, elbows: Vec<Vector2i>) -> Result<(), WireError> {
+        let input_port = graph.free_input_port(output).unwrap_or(0);
+        self.wire_ports_along(graph, input, 0, output, input_port, elbows)
+    }
+
+    /// Like [`Graph::wire_ports_along`]
+    pub fn wire_ports_along(&mut self, graph: &mut Graph, input: NodeId, output_port: usize, output: NodeId, input_port: usize, elbows: Vec<Vector2i>) -> Result<(), WireError> {
+        graph.can_wire_ports(input, output_port, output, input_port)?;
+        let wire = WireHandle { input, output_port, output, input_port };
+        let at = WireAt::of(graph, &wire).expect("wire was checked");
+        let command = Command::Wire { wire: at, index: graph.nodes[output].inputs.len(), elbows };
+        self.apply(graph, command);
+        Ok(())
+    }
+
+    /// Like [`Graph::remove_wire`]
+    pub fn remove_wire(&mut self, graph: &mut Graph, input: NodeId, output: NodeId) -> bool {
+        let wire = graph.node(output).and_then(|node| node.inputs.iter().find(|wire| wire.input == input)).map(|wire| wire.handle(output));
+        wire.is_some_and(|wire| self.unwire(graph, &wire))
+    }
+
+    /// Like [`Graph::unwire`]
+    pub fn unwire(&mut self, graph: &mut Graph, wire: &WireHandle) -> bool {
+        let (Some(at), Some(output)) = (WireAt::of(graph, wire), graph.node(wire.output)) else {
+            return false;
+        };
+        let Some(index) = output.inputs.iter().position(|found| found.handle(wire.output) == *wire) else {
+            return false;
+        };
+        let elbows = output.inputs[index].elbows.clone();
+        self.apply(graph, Command::Unwire { wire: at, index, elbows })
+    }
+
+    /// Like [`Graph::move_node`]
+    pub fn move_node(&mut self, graph: &mut Graph, id: NodeId, position: Vector2i) -> bool {
+        let Some(node) = graph.node(id) else {
+            return false;
+        };
+        let offset = position - node.position;
+        self.move_nodes(graph, &[id], offset)
+    }
+
+    /// Like [`Graph::move_nodes`]. Consecutive moves of the same nodes within a group are recorded as one.
+    pub fn move_nodes(&mut self, graph: &mut Graph, ids: &[NodeId], offset: Vector2i) -> bool {
+        let Some(positions) = ids.iter().map(|&id| graph.node(id).map(Node::position)).collect::<Option<Vec<_>>>() else {
+            return false;
+        };
+        let wires = graph.crossing_wires(ids)
+            .iter()
+            .filter_map(|wire| Some(MovedWire {
+                wire: WireAt::of(graph, wire)?,
+                from: graph.find_wire(wire)?.elbows.clone(),
+                to: graph.followed_elbows(wire, ids, offset),
+            }))
+            .filter(|moved| moved.from != moved.to)
+            .collect();
+        self.apply(graph, Command::MoveNodes { positions, offset, wires })
+    }
+
+    /// Like [`Graph::set_gate`]
+    pub fn set_gate(&mut self, graph: &mut Graph, id: NodeId, gate: Gate) -> Result<(), WireError> {
+        let to_ports = graph.ports_for_gate(id, gate)?;
+        let node = &graph.nodes[id];
+        let from_ports = node.inputs.iter().map(|wire| wire.input_port).collect();
+        let command = Command::SetGate { position: node.position, from: node.gate, to: gate, from_ports, to_ports };
+        self.apply(graph, command);
+        Ok(())
+    }
+
+    /// Like [`Graph::set_label`]
+    pub fn set_label(&mut self, graph: &mut Graph, id: NodeId, label: Option<String>) -> bool {
+        let Some(node) = graph.node(id) else {
+            return false;
+        };
+        let command = Command::SetLabel { position: node.position, from: node.label.clone(), to: label };
+        self.apply(graph, command)
+    }
+
+    /// Reroutes the wire through new elbows. Returns `false` if the wire no longer exists.
+    /// Consecutive changes to the same wire within a group are recorded as one.
+    pub fn set_elbows(&mut self, graph: &mut Graph, wire: &WireHandle, elbows: Vec<Vector2i>) -> bool {
+        let (Some(at), Some(found)) = (WireAt::of(graph, wire), graph.find_wire(wire)) else {
+            return false;
+        };
+        let command = Command::SetElbows { wire: at, from: found.elbows.clone(), to: elbows };
+        self.apply(graph, command)
+    }
+
+    /// Like [`Graph::move_elbow`]
+    pub fn move_elbow(&mut self, graph: &mut Graph, elbow: &ElbowHandle, position: Vector2i) -> bool {
+        self.edit_elbows(graph, elbow, |elbows| elbows[elbow.index] = position)
+    }
+
+    /// Like [`Graph::remove_elbow`]
+    pub fn remove_elbow(&mut self, graph: &mut Graph, elbow: &ElbowHandle) -> bool {
+        self.edit_elbows(graph, elbow, |elbows| { elbows.remove(elbow.index); })
+    }
+
+    fn edit_elbows(&mut self, graph: &mut Graph, elbow: &ElbowHandle, f: impl FnOnce(&mut Vec<Vector2i>)) -> bool {
+        let Some(mut elbows) = graph.find_wire(&elbow.wire).filter(|wire| elbow.index < wire.elbows.len()).map(|wire| wire.elbows.clone()) else {
+            return false;
+        };
+        f(&mut elbows);
+        self.set_elbows(graph, &elbow.wire, elbows)
+    }
+
+    /// Like [`Graph::reroute_wire`]
+    pub fn reroute_wire(&mut self, graph: &mut Graph, wire: &WireHandle) -> bool {
+        let elbows = graph.route_wire(wire.input, wire.output);
+        self.set_elbows(graph, wire, elbows)
+    }
+
+    /// Like [`Graph::reroute_wires`], as a single group
+    pub fn reroute_wires(&mut self, graph: &mut Graph, ids: impl IntoIterator<Item = NodeId>) {
+        self.begin_group();
+        for wire in graph.wires_of(ids) {
+            self.reroute_wire(graph, &wire);
+        }
+        self.end_group();
+    }
+
+    /// Copies every node of `snippet` and the wires between them into the graph as one group,
+    /// shifted so the top left corner of the snippet lands on `at`. Labels the graph already uses are dropped.
+    /// Fails without changing anything if a node would land on an existing one,
+    /// or the snippet's copy of a component differs from the graph's.
+    pub fn paste(&mut self, graph: &mut Graph, snippet: &Graph, at: Vector2i) -> Result<Vec<NodeId>, PasteError> {
+        let Some(min) = snippet.nodes()
+            .map(|(_, node)| node.position)
+            .reduce(|min, position| Vector2i::new(min.x.min(position.x), min.y.min(position.y)))
+        else {
+            return Ok(Vec::new());
+        };
+        let offset = at - min;
+        let overlap = snippet.nodes()
+            .map(|(_, node)| node.position + offset)
+            .find(|&position| graph.find_node_at(position).is_some());
+        if let Some(position) = overlap {
+            return Err(PasteError::Overlap(position));
+        }
+        // Wires into a component's pins only fit if the graph's copy of it has the same pins
+        let mismatch = snippet.nodes()
+            .filter_map(|(_, node)| match node.gate {
+                Gate::Component(id) => snippet.component(id),
+                _ => None,
+            })
+            .find(|component| graph
+                .find_component(component.name())
+                .and_then(|id| graph.component(id))
+                .is_some_and(|existing| !same_circuit(existing.circuit(), component.circuit())));
+        if let Some(component) = mismatch {
+            return Err(PasteError::ComponentMismatch(component.name().to_string()));
+        }
+
+        self.begin_group();
+        for (_, node) in snippet.nodes() {
+            let label = node.label.clone().filter(|label| graph.find_node_by_label(label).is_none());
+            // Components come along with the snippet, unless the graph already has them
+            let gate = match node.gate {
+                Gate::Component(id) => match snippet.component(id) {
+                    Some(component) => Gate::Component(graph.add_component(component.clone())),
+                    None => continue,
+                },
+                gate => gate,
+            };
+            let snapshot = NodeSnapshot { label, ..NodeSnapshot::new(gate, node.position + offset) };
+            self.apply(graph, Command::AddNode(snapshot));
+        }
+        for (_, node) in snippet.nodes() {
+            let output = node.position + offset;
+            for (index, wire) in node.inputs.iter().enumerate() {
+                let Some(input) = snippet.node(wire.input) else { continue };
+                let elbows = wire.elbows.iter().map(|&elbow| elbow + offset).collect();
+                let at = WireAt { input: input.position + offset, output_port: wire.output_port, output, input_port: wire.input_port };
+                self.apply(graph, Command::Wire { wire: at, index, elbows });
+            }
+        }
+        self.end_group();
+        Ok(snippet.nodes().filter_map(|(_, node)| graph.find_node_at(node.position + offset)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::component::Component;
+    use super::*;
+
+    /// Gate, position and label of every node, each with the inputs, ports and elbows of its wires, in a stable order
+    type Layout = Vec<(Vector2i, Gate, Option<String>, Vec<(Vector2i, usize, usize, Vec<Vector2i>)>)>;
+
+    fn layout(graph: &Graph) -> Layout {
+        let mut layout: Layout = graph.nodes()
+            .map(|(_, node)| {
+                let wires = node.inputs
+                    .iter()
+                    .map(|wire| (graph.nodes[wire.input].position, wire.output_port, wire.input_port, wire.elbows.clone()))
+                    .collect();
+                (node.position, node.gate, node.label.clone(), wires)
+            })
+            .collect();
+        layout.sort_by_key(|(position, ..)| (position.x, position.y));
+        layout
+    }
+
+    fn node(graph: &Graph, x: i32, y: i32) -> NodeId {
+        graph.find_node_at(Vector2i::new(x, y)).unwrap()
+    }
+
+    #[test]
+    fn test_undo_redo_each_edit() {
+        let mut graph = Graph::new();
+        let mut history = History::new();
+        history.put_node(&mut graph, Gate::Always, Vector2i::new(0, 0));
+        history.put_node(&mut graph, Gate::Or, Vector2i::new(4, 0));
+        history.put_node(&mut graph, Gate::Not, Vector2i::new(4, 4));
+        let (source, or, not) = (node(&graph, 0, 0), node(&graph, 4, 0), node(&graph, 4, 4));
+        history.wire_along(&mut graph, source, or, vec![Vector2i::new(2, 0)]).unwrap();
+        history.wire_along(&mut graph, or, not, Vec::new()).unwrap();
+
+        let mut layouts = vec![layout(&graph)];
+        history.set_gate(&mut graph, or, Gate::Xor).unwrap();
+        layouts.push(layout(&graph));
+        history.move_node(&mut graph, not, Vector2i::new(6, 4));
+        layouts.push(layout(&graph));
+        history.set_label(&mut graph, not, Some(String::from("q")));
+        layouts.push(layout(&graph));
+        let elbow = graph.find_elbow_at(Vector2i::new(2, 0)).unwrap();
+        history.move_elbow(&mut graph, &elbow, Vector2i::new(2, 1));
+        layouts.push(layout(&graph));
+        history.remove_wire(&mut graph, or, not);
+        layouts.push(layout(&graph));
+        history.remove_node(&mut graph, or);
+        layouts.push(layout(&graph));
+        assert_eq!(graph.node_count(), 2);
+
+        for expected in layouts.iter().rev().skip(1) {
+            assert!(history.undo(&mut graph));
+            assert_eq!(&layout(&graph), expected);
+        }
+        for expected in layouts.iter().skip(1) {
+            assert!(history.redo(&mut graph));
+            assert_eq!(&layout(&graph), expected);
+        }
+        assert!(!history.redo(&mut graph));
+    }
+
+    #[test]
+    fn test_undo_move_restores_outside_wires() {
+        let mut graph = Graph::new();
+        let mut history = History::new();
+        let a = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let b = graph.put_node(Gate::Or,     Vector2i::new(4, 2));
+        graph.wire_along(a, b, Vec::from([Vector2i::new(4, 0)])).unwrap();
+        let before = layout(&graph);
+
+        // Lands the bend on `b` itself, which can't be worked out backwards from where it ends up
+        history.move_node(&mut graph, b, Vector2i::new(6, 0));
+        assert_eq!(graph.nodes[b].inputs[0].elbows, [Vector2i::new(6, 0)]);
+        history.undo(&mut graph);
+        assert_eq!(layout(&graph), before);
+
+        // The steps of a drag, recorded as one
+        history.begin_group();
+        for x in 5..=7 {
+            history.move_node(&mut graph, b, Vector2i::new(x, 2 - x % 2));
+        }
+        history.end_group();
+        let after = layout(&graph);
+        history.undo(&mut graph);
+        assert_eq!(layout(&graph), before);
+        history.redo(&mut graph);
+        assert_eq!(layout(&graph), after);
+    }
+
+    #[test]
+    fn test_undo_stops_when_graph_changed() {
+        let mut graph = Graph::new();
+        let mut history = History::new();
+        history.begin_group();
+        history.put_node(&mut graph, Gate::Always, Vector2i::new(0, 0));
+        history.put_node(&mut graph, Gate::Or, Vector2i::new(2, 0));
+        history.end_group();
+        // Removed behind the history's back, so the group can't be undone
+        graph.remove_node(node(&graph, 0, 0));
+        let before = layout(&graph);
+
+        assert!(!history.undo(&mut graph));
+        assert_eq!(layout(&graph), before, "a group is undone entirely or not at all");
+        assert!(history.can_undo() && !history.can_redo());
+
+        graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        assert!(history.undo(&mut graph));
+        assert_eq!(graph.node_count(), 0);
+        graph.put_node(Gate::Never, Vector2i::new(2, 0));
+        assert!(!history.redo(&mut graph), "the cell is taken");
+        assert_eq!(graph.node_count(), 1);
+        assert!(history.can_redo());
+    }
+
+    #[test]
+    fn test_undo_set_gate_restores_ports() {
+        let mut graph = Graph::new();
+        let mut history = History::new();
+        let a   = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let b   = graph.put_node(Gate::Never,  Vector2i::new(0, 1));
+        let xor = graph.put_node(Gate::Xor,    Vector2i::new(2, 0));
+        history.wire_ports_along(&mut graph, a, 0, xor, 1, Vec::new()).unwrap();
+        history.wire_ports_along(&mut graph, b, 0, xor, 0, Vec::new()).unwrap();
+        let before = layout(&graph);
+
+        history.set_gate(&mut graph, xor, Gate::And).unwrap();
+        history.set_gate(&mut graph, xor, Gate::Xor).unwrap();
+        assert_ne!(layout(&graph), before, "going through an And deals the ports out again");
+        history.undo(&mut graph);
+        history.undo(&mut graph);
+        assert_eq!(layout(&graph), before);
+        history.remove_node(&mut graph, a);
+        history.undo(&mut graph);
+        assert_eq!(layout(&graph), before);
+    }
+
+    #[test]
+    fn test_remove_restores_wire_order() {
+        let mut graph = Graph::new();
+        let mut history = History::new();
+        history.put_node(&mut graph, Gate::Or, Vector2i::new(1, 2));
+        let or = node(&graph, 1, 2);
+        for x in 0..3 {
+            history.put_node(&mut graph, Gate::Never, Vector2i::new(x, 0));
+            let input = node(&graph, x, 0);
+            history.wire_along(&mut graph, input, or, Vec::new()).unwrap();
+        }
+        let before = layout(&graph);
+
+        let middle = node(&graph, 1, 0);
+        history.remove_node(&mut graph, middle);
+        history.undo(&mut graph);
+        assert_eq!(layout(&graph), before);
+    }
+
+    #[test]
+    fn test_groups_undo_together() {
+        let mut graph = Graph::new();
+        let mut history = History::new();
+        history.put_node(&mut graph, Gate::Or, Vector2i::new(0, 0));
+
+        // A drag, one cell at a time
+        let or = node(&graph, 0, 0);
+        history.begin_group();
+        for x in 1..=5 {
+            history.move_node(&mut graph, or, Vector2i::new(x, 0));
+        }
+        history.end_group();
+        assert_eq!(history.undo.back().map(Vec::len), Some(1), "consecutive moves merge");
+
+        // Replacing a node is a removal and a placement
+        history.put_node(&mut graph, Gate::And, Vector2i::new(5, 0));
+        history.undo(&mut graph);
+        assert_eq!(graph.nodes[node(&graph, 5, 0)].gate, Gate::Or);
+        history.undo(&mut graph);
+        assert!(graph.find_node_at(Vector2i::new(5, 0)).is_none());
+        assert!(graph.find_node_at(Vector2i::new(0, 0)).is_some());
+
+        // A new edit forgets what could have been redone
+        history.put_node(&mut graph, Gate::Not, Vector2i::new(9, 9));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_paste() {
+        let mut snippet = Graph::new();
+        let a = snippet.put_node(Gate::Always, Vector2i::new(10, 10));
+        let b = snippet.put_node(Gate::Not, Vector2i::new(12, 11));
+        snippet.wire_along(a, b, vec![Vector2i::new(12, 10)]).unwrap();
+        snippet.set_label(a, Some(String::from("a")));
+
+        let mut graph = Graph::new();
+        let mut history = History::new();
+        history.put_node(&mut graph, Gate::Or, Vector2i::new(0, 0));
+        let existing = node(&graph, 0, 0);
+        graph.set_label(existing, Some(String::from("a")));
+
+        assert_eq!(history.paste(&mut graph, &snippet, Vector2i::new(-2, -1)).err(), Some(PasteError::Overlap(Vector2i::new(0, 0))));
+        assert_eq!(graph.node_count(), 1);
+
+        let pasted = history.paste(&mut graph, &snippet, Vector2i::new(1, 0)).unwrap();
+        assert_eq!(pasted.len(), 2);
+        let (source, not) = (node(&graph, 1, 0), node(&graph, 3, 1));
+        assert_eq!(graph.nodes[source].label(), None, "label was already taken");
+        let not = &graph.nodes[not];
+        assert_eq!(not.inputs[0].input, source);
+        assert_eq!(not.inputs[0].elbows, [Vector2i::new(3, 0)]);
+
+        history.undo(&mut graph);
+        assert_eq!(graph.node_count(), 1, "a paste is undone at once");
+    }
+
+    #[test]
+    fn test_paste_checks_components() {
+        // Without its input labeled, the inverter has no pin for the pasted wire to go into
+        let inverter = |input_label: Option<&str>| {
+            let mut circuit = Graph::new();
+            let input  = circuit.put_node(Gate::Never, Vector2i::new(0, 0));
+            let output = circuit.put_node(Gate::Not, Vector2i::new(2, 0));
+            circuit.set_label(input, input_label.map(String::from));
+            circuit.set_label(output, Some(String::from("out")));
+            circuit.wire(input, output).unwrap();
+            Component::new("inverter", circuit).unwrap()
+        };
+        let mut snippet = Graph::new();
+        let inverter_id = snippet.add_component(inverter(Some("in")));
+        let source = snippet.put_node(Gate::Always, Vector2i::new(0, 0));
+        let placed = snippet.put_node(Gate::Component(inverter_id), Vector2i::new(2, 0));
+        snippet.wire(source, placed).unwrap();
+
+        let mut graph = Graph::new();
+        let mut history = History::new();
+        graph.add_component(inverter(None));
+        assert_eq!(
+            history.paste(&mut graph, &snippet, Vector2i::new(0, 0)).err(),
+            Some(PasteError::ComponentMismatch(String::from("inverter"))),
+        );
+        assert_eq!(graph.node_count(), 0, "nothing is pasted");
+
+        let mut graph = Graph::new();
+        graph.add_component(inverter(Some("in")));
+        history.paste(&mut graph, &snippet, Vector2i::new(0, 0)).unwrap();
+        assert_eq!(graph.nodes[node(&graph, 2, 0)].inputs.len(), 1, "the same component takes the same wires");
+    }
+
+    #[test]
+    fn test_budget_forgets_oldest() {
+        let mut graph = Graph::new();
+        let mut history = History::with_budget(size_of::<Command>() * 3);
+        for x in 0..10 {
+            history.put_node(&mut graph, Gate::Or, Vector2i::new(x, 0));
+        }
+        assert!(history.size <= history.budget);
+        let mut undone = 0;
+        while history.undo(&mut graph) {
+            undone += 1;
+        }
+        assert_eq!(undone, 3);
+        assert_eq!(graph.node_count(), 7);
+    }
+}
@@ -1,16 +1,30 @@
 use std::fmt;
 use raylib::prelude::{Color, RaylibDraw};
 use crate::vector2i::Vector2i;
-use super::wire::Wire;
+use super::{signal::Signal, wire::Wire, Graph, NodeId};
 use gate::Gate;
 
 pub mod gate;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node {
     pub(in crate::graph) inputs: Vec<Wire>,
+    /// Nodes with a wire reading from this one
+    pub(in crate::graph) outputs: Vec<NodeId>,
     pub(in crate::graph) gate: Gate,
-    pub                  position: Vector2i,
+    pub(in crate::graph) position: Vector2i,
+    /// Name used to refer to the node from outside the editor, such as a stimulus file
+    pub(in crate::graph) label: Option<String>,
+    /// Output as of the most recent tick; the first output pin for components
+    pub(in crate::graph) state: Signal,
+    /// Output pushed in from outside in place of the gate's, for the input pins of a component's circuit
+    pub(in crate::graph) forced: Option<Signal>,
+    /// This node's own copy of its component's circuit, holding the state inside it
+    pub(in crate::graph) circuit: Option<Box<Graph>>,
+    /// Whether the node is waiting in the graph's dirty queue
+    pub(in crate::graph) queued: bool,
+    /// Position in evaluation order; wires into a node of equal or lower rank are back-edges
+    pub(in crate::graph) rank: usize,
     pub(in crate::graph) visited: bool,
 }
 
@@ -18,17 +32,43 @@ impl Node {
     pub fn new(gate: Gate, position: Vector2i) -> Self {
         Self {
             inputs: Vec::new(),
+            outputs: Vec::new(),
             gate,
             position,
+            label: None,
+            state: Signal::LOW,
+            forced: None,
+            circuit: None,
+            queued: false,
+            rank: 0,
             visited: false,
         }
     }
 
-    pub fn evaluate(&self) -> bool {
-        let input_states = self.inputs
-            .iter()
-            .filter_map(Wire::evaluate);
+    pub fn gate(&self) -> Gate {
+        self.gate
+    }
+
+    /// Moving a node goes through `Graph::move_node` so the graph can find it again.
+    pub fn position(&self) -> Vector2i {
+        self.position
+    }
+
+    /// Whether the output is high; buses are high if any of their bits are
+    pub fn state(&self) -> bool {
+        self.state.is_high()
+    }
+
+    pub fn value(&self) -> Signal {
+        self.state
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
 
-        self.gate.evaluate(input_states)
+    /// Labels are single words so they can be written in whitespace-separated files.
+    pub fn is_valid_label(label: &str) -> bool {
+        !label.is_empty() && !label.contains(char::is_whitespace)
     }
 }
@@ -1,8 +1,17 @@
+use std::{fmt, str::FromStr};
+use raylib::prelude::Color;
+use crate::graph::{component::ComponentId, signal::Signal};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Gate {
     Always,
     Never,
 
+    /// Flipped by clicking it; holds whether it is on
+    Toggle(bool),
+    /// High only while held down; holds whether it is held
+    Button(bool),
+
     Not,
 
     Xor,
@@ -11,42 +20,292 @@ pub enum Gate {
     Nand,
     Or,
     Nor,
+
+    /// Passes on `a` while `select` is low, and `b` while it is high
+    Mux,
+
+    /// Takes a bus of the given width apart into one output per bit, least significant first
+    Split(u8),
+    /// Puts one input per bit, least significant first, together into a bus of the given width
+    Join(u8),
+
+    /// A copy of a component's circuit; see [`Graph::add_component`](crate::graph::Graph::add_component)
+    Component(ComponentId),
 }
 
 impl Gate {
-    pub const fn max_inputs(&self) -> usize {
+    /// Every gate, in palette order
+    pub const ALL: [Gate; 13] = [
+        Self::Always,
+        Self::Never,
+        Self::Toggle(false),
+        Self::Button(false),
+        Self::Not,
+        Self::Xor,
+        Self::And,
+        Self::Nand,
+        Self::Or,
+        Self::Nor,
+        Self::Mux,
+        Self::Split(8),
+        Self::Join(8),
+    ];
+
+    /// Names of the gate's input ports, in order.
+    /// Components name their ports after their pins, which only the graph they belong to knows;
+    /// see [`Graph::input_port_name`](crate::graph::Graph::input_port_name) and [`Graph::input_count`](crate::graph::Graph::input_count).
+    pub fn input_ports(&self) -> &'static [&'static str] {
         match self {
             | Self::Always
             | Self::Never
-                => 0,
+            | Self::Toggle(_)
+            | Self::Button(_)
+            | Self::Component(_)
+                => &[],
 
             | Self::Not
-                => 1,
-
-            | Self::Xor
-                => 2,
-
             | Self::And
             | Self::Nand
             | Self::Or
             | Self::Nor
-                => usize::MAX,
+                => &["in"],
+
+            | Self::Xor
+                => &["a", "b"],
+
+            | Self::Mux
+                => &["a", "b", "select"],
+
+            | Self::Split(_)
+                => &["in"],
+
+            | Self::Join(width)
+                => bit_ports(*width),
+        }
+    }
+
+    /// Names of the gate's output ports, in order; see [`Gate::input_ports`] for components.
+    pub fn output_ports(&self) -> &'static [&'static str] {
+        match self {
+            Self::Component(_) => &[],
+            Self::Split(width) => bit_ports(*width),
+            _ => &["out"],
+        }
+    }
+
+    /// Whether the gate's one input port takes any number of wires, rather than each port taking one
+    pub const fn is_variadic(&self) -> bool {
+        matches!(self, Self::And | Self::Nand | Self::Or | Self::Nor)
+    }
+
+    /// Components take one input per input pin, which only the graph they belong to knows;
+    /// see [`Graph::max_inputs`](crate::graph::Graph::max_inputs).
+    pub fn max_inputs(&self) -> usize {
+        if self.is_variadic() { usize::MAX } else { self.input_ports().len() }
+    }
+
+    /// How many bits splitters and joiners work with; `None` for other gates, which take any width
+    pub const fn width(&self) -> Option<u8> {
+        match self {
+            Self::Split(width) | Self::Join(width) => Some(*width),
+            _ => None,
+        }
+    }
+
+    /// The gate working with `width` bits instead, clamped to what a [`Signal`] holds; `None` for gates without a width.
+    pub const fn with_width(&self, width: u8) -> Option<Gate> {
+        let width = Signal::new(0, width).width();
+        match self {
+            Self::Split(_) => Some(Self::Split(width)),
+            Self::Join(_) => Some(Self::Join(width)),
+            _ => None,
+        }
+    }
+
+    /// Output given the signal on each wire into the gate, along with the input port the wire goes to.
+    /// Splitters output the whole bus, which each of their outputs reads one bit of.
+    pub fn evaluate(&self, mut inputs: impl Iterator<Item = (usize, Signal)>) -> Signal {
+        match self {
+            Self::Always => Signal::HIGH,
+            Self::Never  => Signal::LOW,
+
+            Self::Toggle(on)   => Signal::bit(*on),
+            Self::Button(held) => Signal::bit(*held),
+
+            Self::Not => {
+                let [x] = port_states(inputs);
+                !x
+            },
+
+            Self::Xor => {
+                let [a, b] = port_states(inputs);
+                a ^ b
+            },
+
+            // With no inputs, And is vacuously true and Or is false
+            Self::And  =>  inputs.map(|(_, x)| x).reduce(|a, b| a & b).unwrap_or(Signal::HIGH),
+            Self::Nand => !inputs.map(|(_, x)| x).reduce(|a, b| a & b).unwrap_or(Signal::HIGH),
+            Self::Or   =>  inputs.map(|(_, x)| x).reduce(|a, b| a | b).unwrap_or(Signal::LOW),
+            Self::Nor  => !inputs.map(|(_, x)| x).reduce(|a, b| a | b).unwrap_or(Signal::LOW),
+
+            Self::Mux => {
+                let [a, b, select] = port_states(inputs);
+                if select.is_high() { b } else { a }
+            },
+
+            Self::Split(width) => {
+                let [bus] = port_states(inputs);
+                bus.resized(*width)
+            },
+            Self::Join(width) => {
+                let bits = inputs
+                    .filter(|&(port, x)| port < *width as usize && x.is_high())
+                    .fold(0, |bits, (port, _)| bits | 1 << port);
+                Signal::new(bits, *width)
+            },
+
+            // Components are stepped by the graph instead
+            Self::Component(_) => Signal::LOW,
+        }
+    }
+
+    /// Whether the gate is a source someone can drive high or low, as opposed to being computed from inputs
+    pub const fn is_input(&self) -> bool {
+        self.driven(false).is_some()
+    }
+
+    /// This gate switched to output `value`; `None` if the gate is computed from its inputs.
+    pub const fn driven(&self, value: bool) -> Option<Gate> {
+        match self {
+            Self::Always | Self::Never => Some(if value { Self::Always } else { Self::Never }),
+            Self::Toggle(_) => Some(Self::Toggle(value)),
+            Self::Button(_) => Some(Self::Button(value)),
+            _ => None,
+        }
+    }
+
+    /// Name of the kind of gate, regardless of any state it holds
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::Never  => "never",
+            Self::Toggle(_) => "toggle",
+            Self::Button(_) => "button",
+            Self::Not    => "not",
+            Self::Xor    => "xor",
+            Self::And    => "and",
+            Self::Nand   => "nand",
+            Self::Or     => "or",
+            Self::Nor    => "nor",
+            Self::Mux    => "mux",
+            Self::Split(_) => "split",
+            Self::Join(_)  => "join",
+            Self::Component(_) => "component",
         }
     }
 
-    pub fn evaluate(&self, mut inputs: impl Iterator<Item = bool>) -> bool {
+    /// Short symbol drawn on top of the node
+    pub const fn glyph(&self) -> &'static str {
         match self {
-            Self::Always => true,
-            Self::Never  => false,
+            Self::Always => "1",
+            Self::Never  => "0",
+            Self::Toggle(_) => "T",
+            Self::Button(_) => "B",
+            Self::Not    => "!",
+            Self::Xor    => "^",
+            Self::And    => "&",
+            Self::Nand   => "!&",
+            Self::Or     => "|",
+            Self::Nor    => "!|",
+            Self::Mux    => "?",
+            Self::Split(_) => "<",
+            Self::Join(_)  => ">",
+            Self::Component(_) => "#",
+        }
+    }
 
-            Self::Not => !inputs.next().unwrap_or_default(),
+    pub const fn color(&self) -> Color {
+        match self {
+            Self::Always => Color::new(230, 230, 230, 255),
+            Self::Never  => Color::new( 90,  90,  90, 255),
+            Self::Toggle(_) => Color::new(240, 200,  70, 255),
+            Self::Button(_) => Color::new(240, 140,  60, 255),
+            Self::Not    => Color::new(230,  90,  90, 255),
+            Self::Xor    => Color::new(180, 110, 230, 255),
+            Self::And    => Color::new( 90, 160, 230, 255),
+            Self::Nand   => Color::new( 60, 100, 160, 255),
+            Self::Or     => Color::new( 90, 210, 120, 255),
+            Self::Nor    => Color::new( 50, 140,  80, 255),
+            Self::Mux    => Color::new(210, 120, 180, 255),
+            Self::Split(_) => Color::new(200, 200, 120, 255),
+            Self::Join(_)  => Color::new(160, 160,  90, 255),
+            Self::Component(_) => Color::new( 70, 190, 190, 255),
+        }
+    }
+}
+
+/// Signal on each of the first `N` input ports; unwired ports read low
+fn port_states<const N: usize>(inputs: impl Iterator<Item = (usize, Signal)>) -> [Signal; N] {
+    let mut states = [Signal::LOW; N];
+    for (port, state) in inputs {
+        if let Some(port_state) = states.get_mut(port) {
+            *port_state = *port_state | state;
+        }
+    }
+    states
+}
+
+/// Names of the first `width` bits of a bus, as ports of splitters and joiners
+fn bit_ports(width: u8) -> &'static [&'static str] {
+    const NAMES: [&str; Signal::MAX_WIDTH as usize] = [
+        "0",  "1",  "2",  "3",  "4",  "5",  "6",  "7",  "8",  "9",  "10", "11", "12", "13", "14", "15",
+        "16", "17", "18", "19", "20", "21", "22", "23", "24", "25", "26", "27", "28", "29", "30", "31",
+        "32", "33", "34", "35", "36", "37", "38", "39", "40", "41", "42", "43", "44", "45", "46", "47",
+        "48", "49", "50", "51", "52", "53", "54", "55", "56", "57", "58", "59", "60", "61", "62", "63",
+    ];
+    &NAMES[..(width as usize).min(NAMES.len())]
+}
+
+impl fmt::Display for Gate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Toggle(true) => f.write_str("toggle-on"),
+            Self::Split(width) | Self::Join(width) => write!(f, "{}-{width}", self.name()),
+            // Buttons are let go of when the circuit is closed
+            _ => f.write_str(self.name()),
+        }
+    }
+}
 
-            Self::Xor => inputs.next().unwrap_or_default() ^ inputs.next().unwrap_or_default(),
+impl FromStr for Gate {
+    type Err = ();
 
-            Self::And  =>  inputs.all(|x| x),
-            Self::Nand => !inputs.all(|x| x),
-            Self::Or   =>  inputs.any(|x| x),
-            Self::Nor  => !inputs.any(|x| x),
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(Self::Always),
+            "never"  => Ok(Self::Never),
+            "toggle"    => Ok(Self::Toggle(false)),
+            "toggle-on" => Ok(Self::Toggle(true)),
+            "button"    => Ok(Self::Button(false)),
+            "not"    => Ok(Self::Not),
+            "xor"    => Ok(Self::Xor),
+            "and"    => Ok(Self::And),
+            "nand"   => Ok(Self::Nand),
+            "or"     => Ok(Self::Or),
+            "nor"    => Ok(Self::Nor),
+            "mux"    => Ok(Self::Mux),
+            _ => {
+                let (name, width) = s.split_once('-').ok_or(())?;
+                let width: u8 = width.parse().map_err(|_| ())?;
+                if !(1..=Signal::MAX_WIDTH).contains(&width) {
+                    return Err(());
+                }
+                match name {
+                    "split" => Ok(Self::Split(width)),
+                    "join"  => Ok(Self::Join(width)),
+                    _ => Err(()),
+                }
+            },
         }
     }
 }
@@ -1,27 +1,28 @@
+use std::{collections::VecDeque, iter::FusedIterator};
+
 use raylib::prelude::RaylibDrawHandle;
 
 use crate::vector2i::Vector2i;
-use super::node::Node;
 
 pub trait Positioned {
     fn position(&self) -> Vector2i;
-}
-
-impl Positioned for Node {
-    fn position(&self) -> Vector2i {
-        self.position
-    }
+    /// Only called by [`InfiniteQuadTree::relocate`], which refiles the item to match.
+    fn set_position(&mut self, position: Vector2i);
 }
 
 impl Positioned for Vector2i {
     fn position(&self) -> Vector2i {
         *self
     }
+
+    fn set_position(&mut self, position: Vector2i) {
+        *self = position;
+    }
 }
 
 const QUAD_TREE_BRANCH_COUNT: usize = 4;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct QuadTreeBranch<T: Positioned> {
     center: Vector2i,
     /// Rows then cols
@@ -29,9 +30,9 @@ struct QuadTreeBranch<T: Positioned> {
 }
 
 impl<T: Positioned> QuadTreeBranch<T> {
-    pub fn new() -> Self {
+    pub fn new(center: Vector2i) -> Self {
         Self {
-            center: Vector2i::default(),
+            center,
             branches: Box::default(),
         }
     }
@@ -47,20 +48,9 @@ impl<T: Positioned> QuadTreeBranch<T> {
     pub fn branch_mut(&mut self, position: &Vector2i) -> &mut Option<InfiniteQuadTree<T>> {
         &mut self.branches[self.branch_containing(position)]
     }
-
-    pub fn insert(&mut self, item: T) -> Option<T> {
-        let branch = self.branch_mut(&item.position());
-        if let Some(subtree) = branch {
-            subtree.insert(item)
-        } else {
-            let subtree = InfiniteQuadTree::new_from(item);
-            branch.insert(subtree);
-            None
-        }
-    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum QuadTreeInner<T: Positioned> {
     Value(Vec<T>),
     Subtree(QuadTreeBranch<T>),
@@ -100,48 +90,104 @@ impl<T: Positioned> QuadTreeInner<T> {
         }
         (Vector2i::new(min_x, min_y), Vector2i::new(max_x, max_y))
     }
+}
 
-    pub fn restructure(&mut self) {
-        println!("restructuring");
-        if let Self::Value(vec) = std::mem::replace(self, Self::Subtree(QuadTreeBranch::new())) {
-            if let Self::Subtree(subtree) = self {
-                {
-                    let (mut center_x, mut center_y) = (0,0);
-                    for position in vec.iter().map(Positioned::position) {
-                        center_x += position.x;
-                        center_y += position.y;
-                    }
-                    let n = vec.len() as i32;
-                    center_x /= n;
-                    center_y /= n;
-                    subtree.center = Vector2i::new(center_x, center_y);
-                }
-                for item in vec {
-                    subtree.insert(item);
-                }
-            } else {
-                panic!("expected the memory I just replaced to have been replaced with what I frickin replaced it with");
-            }
-        } else {
-            unimplemented!("should not restructure subtree, only value")
-        }
+/// Picks the coordinate to split an axis at: the median, so each side gets about half.
+/// Items at or before the split go to one side, so when the median is also the largest value the split moves
+/// down to the next distinct value to keep the other side from being empty.
+/// Returns the only value there is if all are the same, in which case this axis can't separate anything.
+fn split_point(values: &mut [i32]) -> i32 {
+    let middle = (values.len() - 1) / 2;
+    let (_, &mut median, after) = values.select_nth_unstable(middle);
+    if after.iter().any(|&value| value > median) {
+        median
+    } else {
+        values[..middle].iter().copied().filter(|&value| value < median).max().unwrap_or(median)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InfiniteQuadTree<T: Positioned> {
     content: QuadTreeInner<T>,
+    /// Number of items in this tree and all its branches
+    len: usize,
+    /// How many subtrees up the root is
+    depth: usize,
+    /// `len` as of when this tree was last split or rebuilt
+    built_len: usize,
+}
+
+impl<T: Positioned> Default for InfiniteQuadTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T: Positioned> InfiniteQuadTree<T> {
+    /// Values are split into subtrees once they hold this many items
     const RESTRUCTURE_THRESHOLD: usize = 8;
+    /// Subtrees are merged back into a value once they hold fewer than this many items
+    const MERGE_THRESHOLD: usize = Self::RESTRUCTURE_THRESHOLD / 2;
+    /// Trees this deep are never split, no matter how many items they hold
+    pub const MAX_DEPTH: usize = 64;
 
     pub fn new() -> Self {
-        Self { content: QuadTreeInner::default(), }
+        Self::with_depth(0)
+    }
+
+    fn with_depth(depth: usize) -> Self {
+        Self { content: QuadTreeInner::default(), len: 0, depth, built_len: 0 }
     }
 
     pub fn new_from(item: T) -> Self {
-        Self { content: QuadTreeInner::Value(Vec::from([item])) }
+        Self { content: QuadTreeInner::Value(Vec::from([item])), len: 1, depth: 0, built_len: 0 }
+    }
+
+    /// Builds a balanced tree out of items at distinct positions, splitting each level at the median.
+    fn build(items: Vec<T>, depth: usize) -> Self {
+        let len = items.len();
+        if len < Self::RESTRUCTURE_THRESHOLD || depth >= Self::MAX_DEPTH {
+            return Self { content: QuadTreeInner::Value(items), len, depth, built_len: len };
+        }
+
+        let mut xs: Vec<i32> = items.iter().map(|item| item.position().x).collect();
+        let mut ys: Vec<i32> = items.iter().map(|item| item.position().y).collect();
+        let mut subtree = QuadTreeBranch::new(Vector2i::new(split_point(&mut xs), split_point(&mut ys)));
+
+        let mut buckets: [Vec<T>; QUAD_TREE_BRANCH_COUNT] = Default::default();
+        for item in items {
+            buckets[subtree.branch_containing(&item.position())].push(item);
+        }
+        for (branch, bucket) in subtree.branches.iter_mut().zip(buckets) {
+            if !bucket.is_empty() {
+                *branch = Some(Self::build(bucket, depth + 1));
+            }
+        }
+        Self { content: QuadTreeInner::Subtree(subtree), len, depth, built_len: len }
+    }
+
+    /// Rebuilds this tree from scratch, which rebalances it.
+    fn rebuild(&mut self) {
+        let items = self.drain().collect();
+        *self = Self::build(items, self.depth);
+    }
+
+    /// Collapses a subtree that has fewer than [`Self::MERGE_THRESHOLD`] items back into a value.
+    fn merge_if_underfull(&mut self) {
+        if matches!(self.content, QuadTreeInner::Subtree(_)) && self.len < Self::MERGE_THRESHOLD {
+            let len = self.len;
+            let items = self.drain().collect();
+            self.content = QuadTreeInner::Value(items);
+            self.len = len;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     /// Returns whatever item was already there; `None` if the position was free.
@@ -154,8 +200,9 @@ impl<T: Positioned> InfiniteQuadTree<T> {
                     Some(index) => Some(std::mem::replace(&mut vec[index], value)),
                     None => {
                         vec.push(value);
-                        if vec.len() >= Self::RESTRUCTURE_THRESHOLD {
-                            self.content.restructure();
+                        self.len += 1;
+                        if self.len >= Self::RESTRUCTURE_THRESHOLD && self.depth < Self::MAX_DEPTH {
+                            self.rebuild();
                         }
                         None
                     },
@@ -163,11 +210,19 @@ impl<T: Positioned> InfiniteQuadTree<T> {
             },
 
             QuadTreeInner::Subtree(subtree) => {
-                let branch = subtree.branch_mut(&position);
-                match branch {
-                    Some(branch_tree) => branch_tree.insert(value),
-                    None => branch.insert(InfiniteQuadTree::new()).insert(value),
+                let depth = self.depth;
+                let branch = subtree.branch_mut(&position).get_or_insert_with(|| Self::with_depth(depth + 1));
+                let replaced = branch.insert(value);
+                if replaced.is_none() {
+                    self.len += 1;
+                    // Rebuilding once the tree has doubled since the last build keeps the cost amortized,
+                    // and keeping every branch within three quarters of the whole keeps the depth logarithmic
+                    let lopsided = branch.len * 4 > self.len * 3;
+                    if lopsided && self.len >= self.built_len * 2 {
+                        self.rebuild();
+                    }
                 }
+                replaced
             },
         }
     }
@@ -185,54 +240,504 @@ impl<T: Positioned> InfiniteQuadTree<T> {
         }
     }
 
+    /// Moving the item through the returned reference leaves it filed under its old position, so don't.
     pub fn at_mut(&mut self, position: Vector2i) -> Option<&mut T> {
-        todo!()
+        match &mut self.content {
+            QuadTreeInner::Value(vec) => vec
+                .iter_mut()
+                .find(|item| item.position() == position),
+
+            QuadTreeInner::Subtree(subtree) => subtree
+                .branch_mut(&position)
+                .as_mut()
+                .and_then(|branch| branch.at_mut(position)),
+        }
+    }
+
+    pub fn entry(&mut self, position: Vector2i) -> Entry<'_, T> {
+        if self.at(position).is_some() {
+            Entry::Occupied(OccupiedEntry { tree: self, position })
+        } else {
+            Entry::Vacant(VacantEntry { tree: self, position })
+        }
+    }
+
+    /// Inserts the item unless its position is taken, and returns whichever item is there.
+    pub fn get_or_insert(&mut self, item: T) -> &mut T {
+        let position = item.position();
+        self.entry(position).or_insert(item)
+    }
+
+    /// Moves the item at `old` to `new`, updating the item's own position to match.
+    /// Returns `false` without changing anything if there is no item at `old` or another item is at `new`.
+    pub fn relocate(&mut self, old: Vector2i, new: Vector2i) -> bool {
+        if self.at(old).is_none() {
+            return false;
+        }
+        if old == new {
+            return true;
+        }
+        if self.at(new).is_some() {
+            return false;
+        }
+        let mut item = self.remove(old).expect("item should have been found");
+        item.set_position(new);
+        self.insert(item);
+        true
+    }
+
+    /// Keeps only the items for which `f` returns `true`, merging branches left underfull.
+    pub fn retain(&mut self, mut f: impl FnMut(&mut T) -> bool) {
+        self.retain_inner(&mut f);
+    }
+
+    fn retain_inner(&mut self, f: &mut impl FnMut(&mut T) -> bool) {
+        match &mut self.content {
+            QuadTreeInner::Value(vec) => {
+                vec.retain_mut(|item| f(item));
+                self.len = vec.len();
+            },
+
+            QuadTreeInner::Subtree(subtree) => {
+                let mut len = 0;
+                for branch in subtree.branches.iter_mut() {
+                    if let Some(tree) = branch {
+                        tree.retain_inner(f);
+                        len += tree.len;
+                        if tree.is_empty() {
+                            *branch = None;
+                        }
+                    }
+                }
+                self.len = len;
+                self.merge_if_underfull();
+            },
+        }
+    }
+
+    /// Removes every item, leaving the tree empty.
+    pub fn drain(&mut self) -> IntoIter<T> {
+        std::mem::replace(self, Self::with_depth(self.depth)).into_iter()
+    }
+
+    /// Removes and returns the item at the position; `None` if the position was free.
+    /// Branches left empty are dropped, and a subtree left underfull merges back into a value.
+    pub fn remove(&mut self, position: Vector2i) -> Option<T> {
+        let removed = match &mut self.content {
+            QuadTreeInner::Value(vec) => {
+                let index = vec.iter().position(|item| item.position() == position)?;
+                Some(vec.remove(index))
+            },
+
+            QuadTreeInner::Subtree(subtree) => {
+                let branch = subtree.branch_mut(&position);
+                let removed = branch.as_mut()?.remove(position)?;
+                if branch.as_ref().is_some_and(InfiniteQuadTree::is_empty) {
+                    *branch = None;
+                }
+                Some(removed)
+            },
+        };
+        self.len -= 1;
+        self.merge_if_underfull();
+        removed
     }
 }
 
-use std::collections::LinkedList;
+/// A position in an [`InfiniteQuadTree`] that may or may not hold an item
+pub enum Entry<'a, T: Positioned> {
+    Occupied(OccupiedEntry<'a, T>),
+    Vacant(VacantEntry<'a, T>),
+}
 
-pub struct Iter<'a, T: 'a + Positioned> {
-    stack: LinkedList<(&'a QuadTreeInner<T>, usize)>,
+pub struct OccupiedEntry<'a, T: Positioned> {
+    tree: &'a mut InfiniteQuadTree<T>,
+    position: Vector2i,
+}
+
+pub struct VacantEntry<'a, T: Positioned> {
+    tree: &'a mut InfiniteQuadTree<T>,
+    position: Vector2i,
+}
+
+impl<'a, T: Positioned> Entry<'a, T> {
+    pub fn position(&self) -> Vector2i {
+        match self {
+            Self::Occupied(entry) => entry.position,
+            Self::Vacant(entry) => entry.position,
+        }
+    }
+
+    pub fn or_insert(self, item: T) -> &'a mut T {
+        self.or_insert_with(|| item)
+    }
+
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> &'a mut T {
+        match self {
+            Self::Occupied(entry) => entry.into_mut(),
+            Self::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    pub fn and_modify(mut self, f: impl FnOnce(&mut T)) -> Self {
+        if let Self::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, T: Positioned> OccupiedEntry<'a, T> {
+    pub fn get(&self) -> &T {
+        self.tree.at(self.position).expect("occupied entry should hold an item")
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.tree.at_mut(self.position).expect("occupied entry should hold an item")
+    }
+
+    pub fn into_mut(self) -> &'a mut T {
+        self.tree.at_mut(self.position).expect("occupied entry should hold an item")
+    }
+
+    /// Replaces the item, returning the old one
+    pub fn insert(&mut self, item: T) -> T {
+        assert_eq!(item.position(), self.position, "item should be inserted at the entry's position");
+        self.tree.insert(item).expect("occupied entry should hold an item")
+    }
+
+    pub fn remove(self) -> T {
+        self.tree.remove(self.position).expect("occupied entry should hold an item")
+    }
+}
+
+impl<'a, T: Positioned> VacantEntry<'a, T> {
+    pub fn insert(self, item: T) -> &'a mut T {
+        assert_eq!(item.position(), self.position, "item should be inserted at the entry's position");
+        self.tree.insert(item);
+        self.tree.at_mut(self.position).expect("inserted item should be found")
+    }
 }
 
-impl<'a, T: Positioned> Iterator for Iter<'a, T> {
+/// Whether the position lies within the inclusive rectangle
+fn in_rect(position: Vector2i, min: Vector2i, max: Vector2i) -> bool {
+    (min.x..=max.x).contains(&position.x) && (min.y..=max.y).contains(&position.y)
+}
+
+/// Squared distance from the position to the nearest point of the inclusive rectangle
+fn distance_sqr_to_rect(position: Vector2i, min: Vector2i, max: Vector2i) -> i64 {
+    let dx = (min.x as i64 - position.x as i64).max(0) + (position.x as i64 - max.x as i64).max(0);
+    let dy = (min.y as i64 - position.y as i64).max(0) + (position.y as i64 - max.y as i64).max(0);
+    dx * dx + dy * dy
+}
+
+fn distance_sqr(a: Vector2i, b: Vector2i) -> i64 {
+    distance_sqr_to_rect(a, b, b)
+}
+
+impl<T: Positioned> QuadTreeBranch<T> {
+    /// Inclusive bounds of the `index`th branch, given the bounds of this subtree
+    fn branch_bounds(&self, index: usize, (mut min, mut max): (Vector2i, Vector2i)) -> (Vector2i, Vector2i) {
+        // Mirrors `branch_containing`: a set bit means the position is at or before the center on that axis
+        if index & 1 != 0 {
+            max.x = max.x.min(self.center.x);
+        } else {
+            min.x = min.x.max(self.center.x.saturating_add(1));
+        }
+        if index & 2 != 0 {
+            max.y = max.y.min(self.center.y);
+        } else {
+            min.y = min.y.max(self.center.y.saturating_add(1));
+        }
+        (min, max)
+    }
+}
+
+impl<T: Positioned> QuadTreeInner<T> {
+    fn nearest<'a>(&'a self, position: Vector2i, bounds: (Vector2i, Vector2i), best: &mut Option<(i64, &'a T)>) {
+        match self {
+            Self::Value(vec) => {
+                for item in vec {
+                    let distance = distance_sqr(position, item.position());
+                    if !best.is_some_and(|(best_distance, _)| best_distance <= distance) {
+                        *best = Some((distance, item));
+                    }
+                }
+            },
+
+            Self::Subtree(subtree) => {
+                let mut branches: Vec<(i64, (Vector2i, Vector2i), &InfiniteQuadTree<T>)> = subtree.branches
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, branch)| {
+                        let (min, max) = subtree.branch_bounds(index, bounds);
+                        branch.as_ref().map(|branch| (distance_sqr_to_rect(position, min, max), (min, max), branch))
+                    })
+                    .collect();
+                // Closest branches first so the others are more likely to be pruned
+                branches.sort_by_key(|(distance, _, _)| *distance);
+                for (distance, bounds, branch) in branches {
+                    if best.is_some_and(|(best_distance, _)| distance >= best_distance) {
+                        break;
+                    }
+                    branch.content.nearest(position, bounds, best);
+                }
+            },
+        }
+    }
+}
+
+impl<T: Positioned> InfiniteQuadTree<T> {
+    /// Items within the inclusive rectangle from `min` to `max`, skipping branches that lie outside it
+    pub fn query_rect(&self, min: Vector2i, max: Vector2i) -> QueryRect<'_, T> {
+        QueryRect {
+            min,
+            max,
+            stack: Vec::from([(&self.content, (Vector2i::new(i32::MIN, i32::MIN), Vector2i::new(i32::MAX, i32::MAX)))]),
+            values: [].iter(),
+        }
+    }
+
+    /// The item closest to the position by euclidean distance; `None` if the tree is empty.
+    /// Ties are broken arbitrarily.
+    pub fn nearest(&self, position: Vector2i) -> Option<&T> {
+        let mut best = None;
+        self.content.nearest(position, (Vector2i::new(i32::MIN, i32::MIN), Vector2i::new(i32::MAX, i32::MAX)), &mut best);
+        best.map(|(_, item)| item)
+    }
+}
+
+pub struct QueryRect<'a, T: 'a + Positioned> {
+    min: Vector2i,
+    max: Vector2i,
+    /// Trees yet to be visited, with their bounds
+    stack: Vec<(&'a QuadTreeInner<T>, (Vector2i, Vector2i))>,
+    /// Items of the value currently being visited
+    values: std::slice::Iter<'a, T>,
+}
+
+impl<'a, T: Positioned> Iterator for QueryRect<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        use QuadTreeInner::*;
-        if let Some((tree, index)) = self.stack.front_mut() {
-            let tree = *tree;
-            let current_index = *index;
-            *index += 1;
-            let len = match tree {
-                Value(vec) => vec.len(),
-                Subtree(_) => QUAD_TREE_BRANCH_COUNT,
-            };
-            if current_index < len {
-                match tree {
-                    Value(vec) => return Some(&vec[current_index]),
-
-                    Subtree(QuadTreeBranch { center, branches }) =>
-                        if let Some(branch) = branches[current_index].as_ref() {
-                            self.stack.push_front((&branch.content, 0));
-                        },
-                }
-            } else {
-                self.stack.pop_front();
+        let (min, max) = (self.min, self.max);
+        loop {
+            if let Some(item) = self.values.find(|item| in_rect(item.position(), min, max)) {
+                return Some(item);
+            }
+            let (tree, bounds) = self.stack.pop()?;
+            match tree {
+                QuadTreeInner::Value(vec) => self.values = vec.iter(),
+
+                QuadTreeInner::Subtree(subtree) => {
+                    for (index, branch) in subtree.branches.iter().enumerate() {
+                        let Some(branch) = branch else { continue };
+                        let (branch_min, branch_max) = subtree.branch_bounds(index, bounds);
+                        let overlaps = branch_min.x <= max.x && min.x <= branch_max.x
+                                    && branch_min.y <= max.y && min.y <= branch_max.y;
+                        if overlaps {
+                            self.stack.push((&branch.content, (branch_min, branch_max)));
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// What a tree turns out to hold when a walk reaches it
+enum Opened<V, N> {
+    Values(V),
+    Branches([Option<N>; QUAD_TREE_BRANCH_COUNT]),
+}
+
+/// A tree, borrowed or owned, that [`Walk`] can take apart
+trait Walkable: Sized {
+    type Values: DoubleEndedIterator + Default;
+
+    fn open(self) -> Opened<Self::Values, Self>;
+}
+
+impl<'a, T: Positioned> Walkable for &'a QuadTreeInner<T> {
+    type Values = std::slice::Iter<'a, T>;
+
+    fn open(self) -> Opened<Self::Values, Self> {
+        match self {
+            QuadTreeInner::Value(vec) => Opened::Values(vec.iter()),
+            QuadTreeInner::Subtree(subtree) => Opened::Branches(subtree.branches.each_ref().map(|branch| branch.as_ref().map(|tree| &tree.content))),
+        }
+    }
+}
+
+impl<'a, T: Positioned> Walkable for &'a mut QuadTreeInner<T> {
+    type Values = std::slice::IterMut<'a, T>;
+
+    fn open(self) -> Opened<Self::Values, Self> {
+        match self {
+            QuadTreeInner::Value(vec) => Opened::Values(vec.iter_mut()),
+            QuadTreeInner::Subtree(subtree) => Opened::Branches(subtree.branches.each_mut().map(|branch| branch.as_mut().map(|tree| &mut tree.content))),
+        }
+    }
+}
+
+impl<T: Positioned> Walkable for QuadTreeInner<T> {
+    type Values = std::vec::IntoIter<T>;
+
+    fn open(self) -> Opened<Self::Values, Self> {
+        match self {
+            QuadTreeInner::Value(vec) => Opened::Values(vec.into_iter()),
+            QuadTreeInner::Subtree(subtree) => Opened::Branches((*subtree.branches).map(|branch| branch.map(|tree| tree.content))),
+        }
+    }
+}
+
+/// Visits every item of a tree without recursing, from either end.
+/// The items still to come are those left in `front`, then those of every tree in `pending`, then those left in `back`.
+struct Walk<N: Walkable> {
+    front: N::Values,
+    pending: VecDeque<N>,
+    back: N::Values,
+    remaining: usize,
+}
+
+impl<N: Walkable> Walk<N> {
+    fn new(root: N, len: usize) -> Self {
+        Self {
+            front: N::Values::default(),
+            pending: VecDeque::from([root]),
+            back: N::Values::default(),
+            remaining: len,
+        }
+    }
+
+    fn next(&mut self) -> Option<<N::Values as Iterator>::Item> {
+        loop {
+            if let Some(item) = self.front.next() {
+                self.remaining -= 1;
+                return Some(item);
+            }
+            match self.pending.pop_front() {
+                Some(tree) => match tree.open() {
+                    Opened::Values(values) => self.front = values,
+                    Opened::Branches(branches) => {
+                        for branch in branches.into_iter().rev().flatten() {
+                            self.pending.push_front(branch);
+                        }
+                    },
+                },
+                None => {
+                    let item = self.back.next()?;
+                    self.remaining -= 1;
+                    return Some(item);
+                },
+            }
+        }
+    }
+
+    fn next_back(&mut self) -> Option<<N::Values as Iterator>::Item> {
+        loop {
+            if let Some(item) = self.back.next_back() {
+                self.remaining -= 1;
+                return Some(item);
+            }
+            match self.pending.pop_back() {
+                Some(tree) => match tree.open() {
+                    Opened::Values(values) => self.back = values,
+                    Opened::Branches(branches) => {
+                        for branch in branches.into_iter().flatten() {
+                            self.pending.push_back(branch);
+                        }
+                    },
+                },
+                None => {
+                    let item = self.front.next_back()?;
+                    self.remaining -= 1;
+                    return Some(item);
+                },
             }
-            self.next()
-        } else {
-            None
         }
     }
 }
 
+/// Implements the iterator traits for a wrapper around a [`Walk`]
+macro_rules! walk_iterator {
+    ($name:ident<$($lifetime:lifetime)?>, $item:ty) => {
+        impl<$($lifetime,)? T: Positioned> Iterator for $name<$($lifetime,)? T> {
+            type Item = $item;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.walk.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (self.walk.remaining, Some(self.walk.remaining))
+            }
+        }
+
+        impl<$($lifetime,)? T: Positioned> DoubleEndedIterator for $name<$($lifetime,)? T> {
+            fn next_back(&mut self) -> Option<Self::Item> {
+                self.walk.next_back()
+            }
+        }
+
+        impl<$($lifetime,)? T: Positioned> ExactSizeIterator for $name<$($lifetime,)? T> {}
+        impl<$($lifetime,)? T: Positioned> FusedIterator for $name<$($lifetime,)? T> {}
+    };
+}
+
+pub struct Iter<'a, T: 'a + Positioned> {
+    walk: Walk<&'a QuadTreeInner<T>>,
+}
+
+walk_iterator!(Iter<'a>, &'a T);
+
+pub struct IterMut<'a, T: 'a + Positioned> {
+    walk: Walk<&'a mut QuadTreeInner<T>>,
+}
+
+walk_iterator!(IterMut<'a>, &'a mut T);
+
+pub struct IntoIter<T: Positioned> {
+    walk: Walk<QuadTreeInner<T>>,
+}
+
+walk_iterator!(IntoIter<>, T);
+
 impl<T: Positioned> InfiniteQuadTree<T> {
     pub fn iter(&self) -> Iter<'_, T> {
-        Iter {
-            stack: LinkedList::from([(&self.content, 0)]),
-        }
+        Iter { walk: Walk::new(&self.content, self.len) }
+    }
+
+    /// Moving items through the returned references leaves them filed under their old positions, so don't.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { walk: Walk::new(&mut self.content, self.len) }
+    }
+}
+
+impl<T: Positioned> IntoIterator for InfiniteQuadTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { walk: Walk::new(self.content, self.len) }
+    }
+}
+
+impl<'a, T: Positioned> IntoIterator for &'a InfiniteQuadTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T: Positioned> IntoIterator for &'a mut InfiniteQuadTree<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
     }
 }
 
@@ -242,37 +747,16 @@ mod tests {
     use rand::prelude::*;
     use raylib::prelude::*;
 
-    fn debug_next<'a, T: Positioned>(it: &mut Iter<'a, T>, d: &mut impl RaylibDraw) -> Option<&'a T> {
-        use QuadTreeInner::*;
-        if let Some((tree, index)) = it.stack.front_mut() {
-            let tree = *tree;
-            let current_index = *index;
-            *index += 1;
-            let len = match tree {
-                Value(vec) => vec.len(),
-                Subtree(_) => QUAD_TREE_BRANCH_COUNT,
-            };
-            let (Vector2i { x: xmin, y: ymin }, Vector2i { x: xmax, y: ymax }) = tree.region_bounds();
-            let (width, height) = (xmax - xmin, ymax - ymin);
-            d.draw_rectangle_lines(xmin, ymin, width, height, Color::GREENYELLOW);
-            if current_index < len {
-                match tree {
-                    Value(vec) => return Some(&vec[current_index]),
-
-                    Subtree(QuadTreeBranch { center, branches }) => {
-                        d.draw_rectangle(center.x, ymin, 1, height, Color::BLUE);
-                        d.draw_rectangle(xmin, center.y, width, 1, Color::BLUE);
-                        if let Some(branch) = branches[current_index].as_ref() {
-                            it.stack.push_front((&branch.content, 0));
-                        }
-                    },
-                }
-            } else {
-                it.stack.pop_front();
+    fn debug_draw<T: Positioned>(tree: &QuadTreeInner<T>, d: &mut impl RaylibDraw) {
+        let (Vector2i { x: xmin, y: ymin }, Vector2i { x: xmax, y: ymax }) = tree.region_bounds();
+        let (width, height) = (xmax - xmin, ymax - ymin);
+        d.draw_rectangle_lines(xmin, ymin, width, height, Color::GREENYELLOW);
+        if let QuadTreeInner::Subtree(QuadTreeBranch { center, branches }) = tree {
+            d.draw_rectangle(center.x, ymin, 1, height, Color::BLUE);
+            d.draw_rectangle(xmin, center.y, width, 1, Color::BLUE);
+            for branch in branches.iter().flatten() {
+                debug_draw(&branch.content, d);
             }
-            debug_next(it, d)
-        } else {
-            None
         }
     }
 
@@ -326,12 +810,281 @@ mod tests {
                 let mut c = d.begin_mode2D(camera);
                 c.draw_rectangle(0, -1000, 1, 2000, Color::WHITE);
                 c.draw_rectangle(-1000, 0, 2000, 1, Color::WHITE);
-                let mut it = tree.iter();
-                while let Some(item) = debug_next(&mut it, &mut c) {
+                debug_draw(&tree.content, &mut c);
+                for item in tree.iter() {
                     let Vector2i { x, y } = item.position();
                     c.draw_pixel(x, y, Color::ORANGE);
                 }
             }
         }
     }
+
+    #[test]
+    fn test_remove() {
+        const NUM_POINTS: i32 = 40;
+        let mut tree = InfiniteQuadTree::new();
+        for i in 0..NUM_POINTS {
+            tree.insert(Vector2i::new(i * 7 % 23, i));
+        }
+        assert!(matches!(tree.content, QuadTreeInner::Subtree(_)));
+
+        assert_eq!(tree.remove(Vector2i::new(-1, -1)), None, "nothing to remove at a free position");
+        for i in 0..NUM_POINTS {
+            let p = Vector2i::new(i * 7 % 23, i);
+            assert_eq!(tree.remove(p), Some(p));
+            assert_eq!(tree.at(p), None, "removed item should not be found");
+            assert_eq!(tree.iter().count(), (NUM_POINTS - i - 1) as usize);
+        }
+
+        assert!(tree.is_empty());
+        assert!(matches!(&tree.content, QuadTreeInner::Value(vec) if vec.is_empty()), "empty tree should collapse back into a value");
+    }
+
+    fn random_points(count: usize) -> Vec<Vector2i> {
+        let mut r = rand::thread_rng();
+        (0..count)
+            .map(|_| Vector2i::new(r.gen_range(-256..=256), r.gen_range(-256..=256)))
+            .collect()
+    }
+
+    #[test]
+    fn test_query_rect() {
+        let points = random_points(500);
+        let mut tree = InfiniteQuadTree::new();
+        for p in points.iter() {
+            tree.insert(*p);
+        }
+
+        let mut r = rand::thread_rng();
+        for _ in 0..50 {
+            let a = Vector2i::new(r.gen_range(-300..=300), r.gen_range(-300..=300));
+            let b = Vector2i::new(r.gen_range(-300..=300), r.gen_range(-300..=300));
+            let (min, max) = (Vector2i::new(a.x.min(b.x), a.y.min(b.y)), Vector2i::new(a.x.max(b.x), a.y.max(b.y)));
+
+            let mut expected: Vec<Vector2i> = tree.iter().copied().filter(|&p| in_rect(p, min, max)).collect();
+            let mut found: Vec<Vector2i> = tree.query_rect(min, max).copied().collect();
+            expected.sort_by_key(|p| (p.x, p.y));
+            found.sort_by_key(|p| (p.x, p.y));
+            assert_eq!(found, expected, "query {min:?}..={max:?} should match a linear scan");
+        }
+
+        let p = points[0];
+        assert_eq!(tree.query_rect(p, p).collect::<Vec<_>>(), [&p], "single cell query should find exactly that item");
+        assert_eq!(tree.query_rect(Vector2i::new(1000, 1000), Vector2i::new(2000, 2000)).count(), 0);
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut tree = InfiniteQuadTree::new();
+        assert_eq!(tree.nearest(Vector2i::new(0, 0)), None);
+
+        for p in random_points(500) {
+            tree.insert(p);
+        }
+
+        let mut r = rand::thread_rng();
+        for _ in 0..100 {
+            let target = Vector2i::new(r.gen_range(-400..=400), r.gen_range(-400..=400));
+            let expected = tree.iter().map(|&p| distance_sqr(target, p)).min();
+            let found = tree.nearest(target).map(|&p| distance_sqr(target, p));
+            assert_eq!(found, expected, "nearest to {target:?} should be as close as the best of a linear scan");
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Counter {
+        position: Vector2i,
+        count: u32,
+    }
+
+    impl Positioned for Counter {
+        fn position(&self) -> Vector2i {
+            self.position
+        }
+
+        fn set_position(&mut self, position: Vector2i) {
+            self.position = position;
+        }
+    }
+
+    fn counter_tree(count: i32) -> InfiniteQuadTree<Counter> {
+        let mut tree = InfiniteQuadTree::new();
+        for i in 0..count {
+            tree.insert(Counter { position: Vector2i::new(i * 7 % 23, i), count: 0 });
+        }
+        tree
+    }
+
+    #[test]
+    fn test_at_mut_and_entry() {
+        const NUM_POINTS: i32 = 40;
+        let mut tree = counter_tree(NUM_POINTS);
+        for i in 0..NUM_POINTS {
+            tree.at_mut(Vector2i::new(i * 7 % 23, i)).expect("should find exact position").count += i as u32;
+        }
+        assert!(tree.iter().all(|counter| counter.count == counter.position.y as u32));
+        assert!(tree.at_mut(Vector2i::new(-1, -1)).is_none());
+
+        let p = Vector2i::new(7, 1);
+        tree.entry(p).and_modify(|counter| counter.count = 100).or_insert_with(|| panic!("position is occupied"));
+        assert_eq!(tree.at(p).unwrap().count, 100);
+
+        let q = Vector2i::new(-5, -5);
+        assert!(matches!(tree.entry(q), Entry::Vacant(_)));
+        tree.entry(q).and_modify(|_| panic!("position is free")).or_insert_with(|| Counter { position: q, count: 1 }).count += 1;
+        assert_eq!(tree.at(q).unwrap().count, 2);
+        assert_eq!(tree.get_or_insert(Counter { position: q, count: 0 }).count, 2, "existing item should be kept");
+        assert_eq!(tree.iter().count(), NUM_POINTS as usize + 1);
+
+        let Entry::Occupied(entry) = tree.entry(q) else { panic!("position should be occupied") };
+        assert_eq!(entry.remove().count, 2);
+        assert!(tree.at(q).is_none());
+    }
+
+    #[test]
+    fn test_retain_and_drain() {
+        const NUM_POINTS: i32 = 40;
+        let mut tree = counter_tree(NUM_POINTS);
+        tree.retain(|counter| counter.position.y % 2 == 0);
+        assert_eq!(tree.iter().count(), NUM_POINTS as usize / 2);
+        assert!(tree.iter().all(|counter| counter.position.y % 2 == 0));
+        assert!(tree.at(Vector2i::new(7, 1)).is_none());
+        assert!(tree.at(Vector2i::new(14, 2)).is_some());
+
+        let mut drained: Vec<i32> = tree.drain().map(|counter| counter.position.y).collect();
+        drained.sort();
+        assert_eq!(drained, (0..NUM_POINTS).step_by(2).collect::<Vec<_>>());
+        assert!(tree.is_empty());
+
+        let mut tree = counter_tree(NUM_POINTS);
+        tree.retain(|_| false);
+        assert!(matches!(&tree.content, QuadTreeInner::Value(vec) if vec.is_empty()), "emptied tree should collapse back into a value");
+    }
+
+    #[test]
+    fn test_relocate() {
+        const NUM_POINTS: i32 = 40;
+        let mut tree = counter_tree(NUM_POINTS);
+        let (from, to) = (Vector2i::new(7, 1), Vector2i::new(-100, 100));
+        tree.at_mut(from).unwrap().count = 5;
+
+        assert!(tree.relocate(from, to));
+        assert!(tree.at(from).is_none());
+        assert_eq!(tree.at(to), Some(&Counter { position: to, count: 5 }), "item should keep its data and know its new position");
+        assert_eq!(tree.iter().count(), NUM_POINTS as usize);
+
+        assert!(!tree.relocate(from, Vector2i::new(0, 0)), "nothing left to move at the old position");
+        assert!(!tree.relocate(to, Vector2i::new(14, 2)), "new position is taken");
+        assert_eq!(tree.at(to).unwrap().count, 5);
+        assert!(tree.relocate(to, to));
+    }
+
+    /// Deepest subtree, after checking that every tree's bookkeeping is consistent
+    fn check_structure<T: Positioned>(tree: &InfiniteQuadTree<T>) -> usize {
+        match &tree.content {
+            QuadTreeInner::Value(vec) => {
+                assert_eq!(tree.len, vec.len());
+                assert!(vec.len() < InfiniteQuadTree::<T>::RESTRUCTURE_THRESHOLD || tree.depth == InfiniteQuadTree::<T>::MAX_DEPTH, "full values should have been split");
+                tree.depth
+            },
+            QuadTreeInner::Subtree(subtree) => {
+                assert!(tree.len >= InfiniteQuadTree::<T>::MERGE_THRESHOLD, "underfull subtrees should have been merged");
+                let mut len = 0;
+                let mut depth = tree.depth;
+                for (index, branch) in subtree.branches.iter().enumerate() {
+                    if let Some(branch) = branch {
+                        assert_eq!(branch.depth, tree.depth + 1);
+                        assert!(branch.iter().all(|item| subtree.branch_containing(&item.position()) == index), "items should be filed under the branch containing them");
+                        len += branch.len;
+                        depth = depth.max(check_structure(branch));
+                    }
+                }
+                assert_eq!(tree.len, len);
+                depth
+            },
+        }
+    }
+
+    #[test]
+    fn test_depth_is_bounded() {
+        // Sorted insertion is the worst case for a tree that only ever splits where items happened to be so far
+        const SIDE: i32 = 100;
+        let mut tree = InfiniteQuadTree::new();
+        for y in 0..SIDE {
+            for x in 0..SIDE {
+                tree.insert(Vector2i::new(x, y));
+            }
+        }
+        let depth = check_structure(&tree);
+        // Every branch holds at most three quarters of its parent
+        let bound = ((SIDE * SIDE) as f64).log(4.0 / 3.0).ceil() as usize;
+        assert!(depth <= bound, "depth {depth} should be logarithmic, at most {bound}");
+        assert_eq!(tree.len(), (SIDE * SIDE) as usize);
+        for y in 0..SIDE {
+            for x in 0..SIDE {
+                assert!(tree.at(Vector2i::new(x, y)).is_some());
+            }
+        }
+
+        // Everything in one column leaves only one axis to split on
+        let mut column = InfiniteQuadTree::new();
+        for y in 0..SIDE * SIDE {
+            column.insert(Vector2i::new(3, y));
+        }
+        let depth = check_structure(&column);
+        assert!(depth <= bound, "depth {depth} should be logarithmic, at most {bound}");
+    }
+
+    #[test]
+    fn test_underfull_subtrees_merge() {
+        const NUM_POINTS: i32 = 200;
+        let mut tree = InfiniteQuadTree::new();
+        for i in 0..NUM_POINTS {
+            tree.insert(Vector2i::new(i * 7 % 23, i));
+        }
+        for i in 0..NUM_POINTS - 3 {
+            tree.remove(Vector2i::new(i * 7 % 23, i));
+            check_structure(&tree);
+        }
+        assert_eq!(tree.len(), 3);
+        assert!(matches!(&tree.content, QuadTreeInner::Value(vec) if vec.len() == 3), "few enough items should fit in a single value");
+    }
+
+    #[test]
+    fn test_iterators() {
+        const NUM_POINTS: i32 = 200;
+        let mut tree = counter_tree(NUM_POINTS);
+        let forward: Vec<Vector2i> = tree.iter().map(|counter| counter.position).collect();
+        let mut backward: Vec<Vector2i> = tree.iter().rev().map(|counter| counter.position).collect();
+        backward.reverse();
+        assert_eq!(forward.len(), NUM_POINTS as usize);
+        assert_eq!(forward, backward, "both ends should walk the same order");
+
+        // Alternating ends should meet in the middle without skipping or repeating anything
+        let mut it = tree.iter();
+        let mut alternating = Vec::new();
+        for i in 0.. {
+            assert_eq!(it.len(), NUM_POINTS as usize - alternating.len());
+            let item = if i % 3 == 0 { it.next_back() } else { it.next() };
+            let Some(item) = item else { break };
+            alternating.push(item.position);
+        }
+        assert_eq!(it.next(), None);
+        alternating.sort_by_key(|p| (p.x, p.y));
+        let mut sorted = forward.clone();
+        sorted.sort_by_key(|p| (p.x, p.y));
+        assert_eq!(alternating, sorted);
+
+        for counter in tree.iter_mut() {
+            counter.count = counter.position.y as u32 * 2;
+        }
+        assert!(tree.iter().all(|counter| counter.count == counter.position.y as u32 * 2));
+
+        let owned: Vec<Vector2i> = tree.into_iter().map(|counter| counter.position).collect();
+        assert_eq!(owned, forward);
+
+        let empty: InfiniteQuadTree<Vector2i> = InfiniteQuadTree::new();
+        assert_eq!(empty.iter().size_hint(), (0, Some(0)));
+        assert_eq!(empty.iter().next_back(), None);
+    }
 }
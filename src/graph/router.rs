@@ -0,0 +1,129 @@
+//! Orthogonal wire routing over the grid.
+//!
+//! Paths move one cell at a time along rows and columns. Each bend costs as much as [`BEND_COST`] cells of length,
+//! so among paths around the same obstacles the one with the fewest bends wins.
+
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap}};
+use crate::vector2i::Vector2i;
+
+/// Extra cost of turning, in cells
+pub const BEND_COST: u32 = 8;
+/// How far outside the box spanned by the two ends a path may stray
+pub const MARGIN: i32 = 16;
+/// Cells explored before giving up
+pub const MAX_EXPANSIONS: usize = 50_000;
+
+const DIRECTIONS: [Vector2i; 4] = [
+    Vector2i::new( 1,  0),
+    Vector2i::new(-1,  0),
+    Vector2i::new( 0,  1),
+    Vector2i::new( 0, -1),
+];
+
+/// A cell plus the index of the direction it was entered from, since that decides whether the next step bends
+type State = (Vector2i, Option<usize>);
+
+/// Lowest possible cost from `from` to `to`, ignoring obstacles
+fn estimate(from: Vector2i, to: Vector2i) -> u32 {
+    let (dx, dy) = (from.x.abs_diff(to.x), from.y.abs_diff(to.y));
+    dx + dy + if dx != 0 && dy != 0 { BEND_COST } else { 0 }
+}
+
+/// Finds the cheapest orthogonal path from `start` to `end` that avoids every cell `is_blocked` returns `true` for,
+/// other than the two ends themselves.
+/// Returns the cells where the path bends, which is what a wire stores as its elbows,
+/// or `None` if there is no such path within [`MARGIN`] of the ends.
+pub fn route(start: Vector2i, end: Vector2i, is_blocked: impl Fn(Vector2i) -> bool) -> Option<Vec<Vector2i>> {
+    let min = Vector2i::new(start.x.min(end.x).saturating_sub(MARGIN), start.y.min(end.y).saturating_sub(MARGIN));
+    let max = Vector2i::new(start.x.max(end.x).saturating_add(MARGIN), start.y.max(end.y).saturating_add(MARGIN));
+    let in_bounds = |p: Vector2i| (min.x..=max.x).contains(&p.x) && (min.y..=max.y).contains(&p.y);
+
+    let mut best: HashMap<State, u32> = HashMap::from([((start, None), 0)]);
+    let mut came_from: HashMap<State, State> = HashMap::new();
+    let mut open = BinaryHeap::from([Reverse((estimate(start, end), 0, (start.x, start.y), None))]);
+    let mut expansions = 0;
+
+    while let Some(Reverse((_, cost, (x, y), direction))) = open.pop() {
+        let cell = Vector2i::new(x, y);
+        let state = (cell, direction);
+        if best.get(&state).is_some_and(|&best_cost| best_cost < cost) {
+            continue;
+        }
+        if cell == end {
+            return Some(bends(&came_from, state));
+        }
+        expansions += 1;
+        if expansions > MAX_EXPANSIONS {
+            return None;
+        }
+
+        for (next_direction, step) in DIRECTIONS.iter().enumerate() {
+            // Doubling back is never shorter
+            if direction.is_some_and(|direction| direction ^ 1 == next_direction) {
+                continue;
+            }
+            let next = cell + *step;
+            if !in_bounds(next) || (next != end && is_blocked(next)) {
+                continue;
+            }
+            let bend = direction.is_some_and(|direction| direction != next_direction);
+            let next_cost = cost + 1 + if bend { BEND_COST } else { 0 };
+            let next_state = (next, Some(next_direction));
+            if best.get(&next_state).is_none_or(|&best_cost| next_cost < best_cost) {
+                best.insert(next_state, next_cost);
+                came_from.insert(next_state, state);
+                open.push(Reverse((next_cost + estimate(next, end), next_cost, (next.x, next.y), Some(next_direction))));
+            }
+        }
+    }
+    None
+}
+
+/// Walks back from `state` to the start, collecting the cells where the direction changes
+fn bends(came_from: &HashMap<State, State>, mut state: State) -> Vec<Vector2i> {
+    let mut bends = Vec::new();
+    while let Some(&previous) = came_from.get(&state) {
+        // The previous cell is a bend if the path entered it going another way than it left
+        if previous.1.is_some() && previous.1 != state.1 {
+            bends.push(previous.0);
+        }
+        state = previous;
+    }
+    bends.reverse();
+    bends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_straight_and_single_bend() {
+        let free = |_| false;
+        assert_eq!(route(Vector2i::new(0, 0), Vector2i::new(5, 0), free), Some(Vec::new()));
+        let elbows = route(Vector2i::new(0, 0), Vector2i::new(5, 3), free).unwrap();
+        assert_eq!(elbows.len(), 1, "an unobstructed L needs exactly one bend");
+        assert!([Vector2i::new(5, 0), Vector2i::new(0, 3)].contains(&elbows[0]));
+    }
+
+    #[test]
+    fn test_avoids_blocked_cells() {
+        // A wall between the ends with a gap at y = 3, which the fewest bends reach straight down from the start
+        let wall = |p: Vector2i| p.x == 3 && p.y != 3;
+        assert_eq!(route(Vector2i::new(0, 0), Vector2i::new(6, 0), wall), Some(Vec::from([Vector2i::new(0, 3), Vector2i::new(6, 3)])));
+    }
+
+    #[test]
+    fn test_ends_may_be_blocked() {
+        let (start, end) = (Vector2i::new(0, 0), Vector2i::new(4, 0));
+        // Only the row between the ends is free, and the ends themselves are not
+        let blocked = |p: Vector2i| p.y != 0 || p == start || p == end;
+        assert_eq!(route(start, end, blocked), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_no_path() {
+        let ring = |p: Vector2i| p.x.abs().max(p.y.abs()) == 2;
+        assert_eq!(route(Vector2i::new(0, 0), Vector2i::new(10, 0), ring), None);
+    }
+}
@@ -0,0 +1,461 @@
+//! Plain text circuit format.
+//!
+//! ```text
+//! electron-architect 6
+//! component <name>
+//! ...
+//! end
+//! node <id> <gate or component name> <x> <y>
+//! label <id> <name>
+//! wire <input id>[:<output port>] <output id>[:<input port>] [<x>,<y> ...]
+//! ```
+//!
+//! Version 2 added `label`, version 3 added the `toggle`, `toggle-on` and `button` gates,
+//! version 4 added components, version 5 added input ports and the `mux` gate, and version 6 added the `split-<width>`
+//! and `join-<width>` gates. Older versions are still accepted.
+//!
+//! A `component` block holds the component's circuit in the same format, minus the header, and has to come before
+//! any node placing it. Wires connect the first port at either end unless another one is given.
+//! Before version 5, wires went to the first free input port of their output, in the order they were listed.
+//!
+//! Node IDs are only meaningful within a single file. Wires are listed in the order they were connected.
+//! Blank lines and lines starting with `#` are ignored.
+//!
+//! Clipboard snippets use the same format, holding just the copied nodes and the wires between them.
+
+use std::{collections::HashMap, error::Error, fmt, io::{self, BufRead, Write}};
+use crate::vector2i::Vector2i;
+use super::{component::{Component, ComponentError}, node::{gate::Gate, Node}, wire::WireError, Graph, NodeId};
+
+pub const HEADER: &str = "electron-architect";
+pub const VERSION: u32 = 6;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    MissingHeader,
+    UnsupportedVersion(u32),
+    Syntax { line: usize, message: String },
+    UnknownNode { line: usize, id: usize },
+    DuplicateNode { line: usize, id: usize },
+    DuplicateLabel { line: usize, label: String },
+    OverlappingNode { line: usize, position: Vector2i },
+    InvalidWire { line: usize, error: WireError },
+    DuplicateComponent { line: usize, name: String },
+    InvalidComponent { line: usize, error: ComponentError },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::MissingHeader => write!(f, "not an {HEADER} file"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported format version {version} (newest supported is {VERSION})"),
+            Self::Syntax { line, message } => write!(f, "line {line}: {message}"),
+            Self::UnknownNode { line, id } => write!(f, "line {line}: no node with id {id}"),
+            Self::DuplicateNode { line, id } => write!(f, "line {line}: node id {id} is already in use"),
+            Self::DuplicateLabel { line, label } => write!(f, "line {line}: label `{label}` is already in use"),
+            Self::OverlappingNode { line, position: Vector2i { x, y } } => write!(f, "line {line}: another node is already at {x} {y}"),
+            Self::InvalidWire { line, error } => write!(f, "line {line}: {error}"),
+            Self::DuplicateComponent { line, name } => write!(f, "line {line}: component `{name}` is already defined"),
+            Self::InvalidComponent { line, error } => write!(f, "line {line}: {error}"),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+fn parse<T: std::str::FromStr>(token: Option<&str>, what: &str, line: usize) -> Result<T, LoadError> {
+    let token = token.ok_or_else(|| LoadError::Syntax { line, message: format!("expected {what}") })?;
+    token.parse().map_err(|_| LoadError::Syntax { line, message: format!("invalid {what} `{token}`") })
+}
+
+fn parse_elbow(token: &str, line: usize) -> Result<Vector2i, LoadError> {
+    let (x, y) = token
+        .split_once(',')
+        .ok_or_else(|| LoadError::Syntax { line, message: format!("invalid elbow `{token}`") })?;
+    Ok(Vector2i::new(parse(Some(x), "elbow x", line)?, parse(Some(y), "elbow y", line)?))
+}
+
+/// Splits one end of a wire into the node ID and the port the wire connects to, if given
+fn parse_wire_end(token: Option<&str>, end: &str, line: usize) -> Result<(usize, Option<usize>), LoadError> {
+    match token.and_then(|token| token.split_once(':')) {
+        Some((id, port)) => Ok((parse(Some(id), &format!("{end} id"), line)?, Some(parse(Some(port), &format!("{end} port"), line)?))),
+        None => Ok((parse(token, &format!("{end} id"), line)?, None)),
+    }
+}
+
+impl Graph {
+    pub fn save(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.save_nodes(self.nodes.ids(), writer)
+    }
+
+    /// Saves just the given nodes and the wires between them, such as for a clipboard snippet
+    pub fn save_nodes(&self, nodes: impl IntoIterator<Item = NodeId>, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "{HEADER} {VERSION}")?;
+        self.write_records(nodes, writer)
+    }
+
+    /// Writes everything but the header: the components the nodes place, the nodes, then the wires between them
+    fn write_records(&self, nodes: impl IntoIterator<Item = NodeId>, writer: &mut impl Write) -> io::Result<()> {
+        // Nodes that are no longer in the graph are skipped
+        let nodes: Vec<_> = nodes.into_iter().filter_map(|id| Some((id, self.node(id)?))).collect();
+
+        let mut components: Vec<_> = nodes
+            .iter()
+            .filter_map(|(_, node)| match node.gate {
+                Gate::Component(id) => self.component(id),
+                _ => None,
+            })
+            .collect();
+        components.sort_by(|a, b| a.name.cmp(&b.name));
+        components.dedup_by(|a, b| a.name == b.name);
+        for component in components {
+            writeln!(writer, "component {}", component.name)?;
+            component.circuit.write_records(component.circuit.nodes.ids(), writer)?;
+            writeln!(writer, "end")?;
+        }
+
+        let mut file_ids: HashMap<NodeId, usize> = HashMap::new();
+        for (file_id, (id, node)) in nodes.iter().enumerate() {
+            file_ids.insert(*id, file_id);
+            let Vector2i { x, y } = node.position;
+            match node.gate {
+                Gate::Component(_) => writeln!(writer, "node {file_id} {} {x} {y}", self.gate_name(node.gate))?,
+                gate => writeln!(writer, "node {file_id} {gate} {x} {y}")?,
+            }
+            if let Some(label) = node.label() {
+                writeln!(writer, "label {file_id} {label}")?;
+            }
+        }
+
+        for (id, node) in nodes.iter() {
+            let output_id = file_ids[id];
+            for wire in node.inputs.iter() {
+                // Wires from nodes that weren't saved have nothing to refer to
+                let Some(&input_id) = file_ids.get(&wire.input) else {
+                    continue;
+                };
+                write!(writer, "wire {input_id}")?;
+                if wire.output_port != 0 {
+                    write!(writer, ":{}", wire.output_port)?;
+                }
+                write!(writer, " {output_id}")?;
+                if wire.input_port != 0 {
+                    write!(writer, ":{}", wire.input_port)?;
+                }
+                for Vector2i { x, y } in wire.elbows.iter() {
+                    write!(writer, " {x},{y}")?;
+                }
+                writeln!(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load(reader: impl BufRead) -> Result<Self, LoadError> {
+        let mut lines = reader.lines().enumerate().filter_map(|(index, line)| {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let line = line.trim();
+            (!line.is_empty() && !line.starts_with('#')).then(|| Ok((index + 1, line.to_string())))
+        });
+
+        let (line_number, header) = lines.next().ok_or(LoadError::MissingHeader)??;
+        let mut tokens = header.split_whitespace();
+        if tokens.next() != Some(HEADER) {
+            return Err(LoadError::MissingHeader);
+        }
+        let version: u32 = parse(tokens.next(), "version", line_number)?;
+        if version > VERSION {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+
+        Self::load_records(&mut lines, version, None)
+    }
+
+    /// Reads records written in format `version` up to the end of the file,
+    /// or up to the `end` of the component block started on line `block`.
+    fn load_records(lines: &mut impl Iterator<Item = io::Result<(usize, String)>>, version: u32, block: Option<usize>) -> Result<Self, LoadError> {
+        let mut graph = Graph::new();
+        let mut nodes: HashMap<usize, NodeId> = HashMap::new();
+
+        while let Some(line) = lines.next() {
+            let (line_number, line) = line?;
+            let mut tokens = line.split_whitespace();
+            let keyword = tokens.next();
+            let no_more_tokens = |mut tokens: std::str::SplitWhitespace| match tokens.next() {
+                Some(extra) => Err(LoadError::Syntax { line: line_number, message: format!("unexpected `{extra}`") }),
+                None => Ok(()),
+            };
+
+            match keyword {
+                Some("node") => {
+                    let id: usize = parse(tokens.next(), "node id", line_number)?;
+                    let gate = match tokens.clone().next().and_then(|name| graph.find_component(name)) {
+                        Some(component) => {
+                            tokens.next();
+                            Gate::Component(component)
+                        },
+                        None => parse(tokens.next(), "gate", line_number)?,
+                    };
+                    let x: i32 = parse(tokens.next(), "x", line_number)?;
+                    let y: i32 = parse(tokens.next(), "y", line_number)?;
+                    let position = Vector2i::new(x, y);
+                    no_more_tokens(tokens)?;
+                    if nodes.contains_key(&id) {
+                        return Err(LoadError::DuplicateNode { line: line_number, id });
+                    }
+                    if graph.find_node_at(position).is_some() {
+                        return Err(LoadError::OverlappingNode { line: line_number, position });
+                    }
+                    nodes.insert(id, graph.add_node(Node::new(gate, position)));
+                },
+
+                Some("label") => {
+                    let id: usize = parse(tokens.next(), "node id", line_number)?;
+                    let label: String = parse(tokens.next(), "label", line_number)?;
+                    no_more_tokens(tokens)?;
+                    let node = nodes.get(&id).copied().ok_or(LoadError::UnknownNode { line: line_number, id })?;
+                    if !graph.set_label(node, Some(label.clone())) {
+                        return Err(LoadError::DuplicateLabel { line: line_number, label });
+                    }
+                },
+
+                Some("wire") => {
+                    let node_by_id = |id: usize| nodes.get(&id).copied().ok_or(LoadError::UnknownNode { line: line_number, id });
+                    let (input_id, output_port) = parse_wire_end(tokens.next(), "input", line_number)?;
+                    let (output_id, input_port) = parse_wire_end(tokens.next(), "output", line_number)?;
+                    let (input, output) = (node_by_id(input_id)?, node_by_id(output_id)?);
+                    let elbows = tokens
+                        .map(|token| parse_elbow(token, line_number))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let input_port = match input_port {
+                        Some(port) => port,
+                        None if version < 5 => graph.free_input_port(output).unwrap_or(0),
+                        None => 0,
+                    };
+                    let output_port = output_port.unwrap_or(0);
+                    graph
+                        .can_wire_ports(input, output_port, output, input_port)
+                        .map_err(|error| LoadError::InvalidWire { line: line_number, error })?;
+                    graph.connect(input, output_port, output, input_port, elbows);
+                },
+
+                Some("component") => {
+                    let name: String = parse(tokens.next(), "component name", line_number)?;
+                    no_more_tokens(tokens)?;
+                    if graph.find_component(&name).is_some() {
+                        return Err(LoadError::DuplicateComponent { line: line_number, name });
+                    }
+                    let circuit = Self::load_records(lines, version, Some(line_number))?;
+                    let component = Component::new(name, circuit).map_err(|error| LoadError::InvalidComponent { line: line_number, error })?;
+                    graph.add_component(component);
+                },
+
+                Some("end") => {
+                    no_more_tokens(tokens)?;
+                    return match block {
+                        Some(_) => Ok(graph),
+                        None => Err(LoadError::Syntax { line: line_number, message: String::from("`end` without a component") }),
+                    };
+                },
+
+                Some(other) => return Err(LoadError::Syntax { line: line_number, message: format!("unknown record `{other}`") }),
+                None => unreachable!("blank lines are skipped"),
+            }
+        }
+
+        match block {
+            Some(line) => Err(LoadError::Syntax { line, message: String::from("component is missing its `end`") }),
+            None => Ok(graph),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(graph: &Graph) -> (String, Graph) {
+        let mut text = Vec::new();
+        graph.save(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let loaded = Graph::load(text.as_bytes()).unwrap();
+        (text, loaded)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut graph = Graph::new();
+        let a = graph.put_node(Gate::Toggle(true), Vector2i::new(0, 0));
+        let b = graph.put_node(Gate::Not,          Vector2i::new(4, -2));
+        let c = graph.put_node(Gate::Xor,    Vector2i::new(8, 3));
+        graph.wire(a, c).unwrap();
+        graph.wire(b, c).unwrap();
+        graph.wire(a, b).unwrap();
+        assert!(graph.set_label(c, Some(String::from("out"))));
+        graph.settle(10);
+
+        let (text, mut loaded) = round_trip(&graph);
+        assert!(text.starts_with("electron-architect 6\n"));
+        assert_eq!(text, round_trip(&loaded).0, "saving should be stable across a round trip");
+
+        let loaded_c = loaded.find_node_at(Vector2i::new(8, 3)).unwrap();
+        assert_eq!(loaded.nodes[loaded_c].gate(), Gate::Xor);
+        assert_eq!(loaded.nodes[loaded_c].label(), Some("out"));
+        assert_eq!(loaded.nodes[loaded.find_node_at(Vector2i::new(0, 0)).unwrap()].gate(), Gate::Toggle(true));
+        let inputs: Vec<Vector2i> = loaded.nodes[loaded_c].inputs
+            .iter()
+            .map(|wire| loaded.nodes[wire.input].position)
+            .collect();
+        assert_eq!(inputs, [Vector2i::new(0, 0), Vector2i::new(4, -2)], "input order should be preserved");
+        assert_eq!(loaded.nodes[loaded_c].inputs[1].elbows, [Vector2i::new(8, -2)]);
+
+        loaded.settle(10);
+        assert_eq!(loaded.nodes[loaded_c].state(), graph.nodes[c].state());
+    }
+
+    #[test]
+    fn test_save_nodes_keeps_internal_wires() {
+        let mut graph = Graph::new();
+        let a = graph.put_node(Gate::Always, Vector2i::new(0, 0));
+        let b = graph.put_node(Gate::Not, Vector2i::new(2, 0));
+        let c = graph.put_node(Gate::Not, Vector2i::new(4, 0));
+        graph.wire_along(a, b, vec![Vector2i::new(1, 1)]).unwrap();
+        graph.wire(b, c).unwrap();
+
+        let mut text = Vec::new();
+        graph.save_nodes([a, b], &mut text).unwrap();
+        let snippet = Graph::load(text.as_slice()).unwrap();
+        assert_eq!(snippet.node_count(), 2);
+        let wires: Vec<_> = snippet.nodes().flat_map(|(_, node)| node.inputs.iter().map(|wire| wire.elbows.clone())).collect();
+        assert_eq!(wires, [vec![Vector2i::new(1, 1)]]);
+    }
+
+    #[test]
+    fn test_components_round_trip() {
+        // An inverter, wrapped in a buffer made of two of them
+        let text = "\
+            electron-architect 4\n\
+            component buffer\n\
+            component inverter\n\
+            node 0 never 0 0\nlabel 0 in\n\
+            node 1 not 1 0\nlabel 1 out\n\
+            wire 0 1\n\
+            end\n\
+            node 0 never 0 0\nlabel 0 in\n\
+            node 1 inverter 1 0\n\
+            node 2 inverter 2 0\nlabel 2 out\n\
+            wire 0 1\nwire 1 2\n\
+            end\n\
+            node 0 toggle-on 0 0\n\
+            node 1 buffer 2 0\n\
+            node 2 or 4 0\n\
+            wire 0 1\nwire 1:0 2 3,0\n";
+        let mut graph = Graph::load(text.as_bytes()).unwrap();
+        let buffer = graph.find_component("buffer").unwrap();
+        assert!(graph.component(buffer).unwrap().circuit().find_component("inverter").is_some(), "components can nest");
+        let or = graph.find_node_at(Vector2i::new(4, 0)).unwrap();
+        graph.settle(10);
+        assert!(graph.nodes[or].state(), "the toggle should come through both inverters");
+
+        let (saved, loaded) = round_trip(&graph);
+        assert!(saved.contains("component buffer\ncomponent inverter\n"));
+        assert!(saved.contains("node 1 buffer 2 0\n"));
+        assert_eq!(saved, round_trip(&loaded).0);
+
+        let load = |text: &str| Graph::load(text.as_bytes()).expect_err("load should fail");
+        assert!(matches!(load("electron-architect 4\ncomponent a\nnode 0 not 0 0"), LoadError::Syntax { line: 2, .. }));
+        assert!(matches!(load("electron-architect 4\nend"), LoadError::Syntax { line: 2, .. }));
+        assert!(matches!(
+            load("electron-architect 4\ncomponent a\nend"),
+            LoadError::InvalidComponent { line: 2, error: ComponentError::NoOutputs },
+        ));
+        assert!(matches!(
+            load("electron-architect 4\ncomponent and\nend"),
+            LoadError::InvalidComponent { line: 2, error: ComponentError::InvalidName(_) },
+        ));
+        let inverter = "component inverter\nnode 0 not 0 0\nlabel 0 out\nend\n";
+        assert!(matches!(
+            load(&format!("electron-architect 4\n{inverter}{inverter}")),
+            LoadError::DuplicateComponent { line: 6, .. },
+        ));
+        assert!(matches!(
+            load(&format!("electron-architect 4\n{inverter}node 0 inverter 0 0\nnode 1 or 1 0\nwire 0:1 1")),
+            LoadError::InvalidWire { line: 8, error: WireError::NoSuchOutput { outputs: 1 } },
+        ));
+    }
+
+    #[test]
+    fn test_wire_ports() {
+        let ports = |text: &str| {
+            let graph = Graph::load(text.as_bytes()).unwrap();
+            let mux = graph.find_node_at(Vector2i::new(2, 0)).unwrap();
+            graph.nodes[mux].inputs.iter().map(|wire| wire.input_port).collect::<Vec<_>>()
+        };
+        let nodes = "node 0 never 0 0\nnode 1 never 0 1\nnode 2 mux 2 0\n";
+        assert_eq!(ports(&format!("electron-architect 5\n{nodes}wire 0 2:2\nwire 1 2\n")), [2, 0]);
+        assert_eq!(ports("electron-architect 4\nnode 0 never 0 0\nnode 1 never 0 1\nnode 2 xor 2 0\nwire 1 2\nwire 0 2\n"), [0, 1], "older files fill ports in order");
+
+        let text = format!("electron-architect 5\n{nodes}wire 0 2:1\nwire 1 2:1\n");
+        assert!(matches!(
+            Graph::load(text.as_bytes()).expect_err("port is taken"),
+            LoadError::InvalidWire { line: 6, error: WireError::PortTaken },
+        ));
+
+        let graph = Graph::load(format!("electron-architect 5\n{nodes}wire 0 2:1\n").as_bytes()).unwrap();
+        assert!(round_trip(&graph).0.contains(" 2:1\n"));
+
+        let buses = "electron-architect 6\nnode 0 join-16 0 0\nnode 1 split-16 2 0\nnode 2 or 4 0\nwire 0 1\nwire 1:15 2\n";
+        let graph = Graph::load(buses.as_bytes()).unwrap();
+        assert_eq!(graph.nodes[graph.find_node_at(Vector2i::new(2, 0)).unwrap()].gate(), Gate::Split(16));
+        assert_eq!(round_trip(&graph).0, buses);
+        assert!(matches!(
+            Graph::load("electron-architect 6\nnode 0 split-65 0 0".as_bytes()).expect_err("too wide"),
+            LoadError::Syntax { line: 2, .. },
+        ));
+    }
+
+    #[test]
+    fn test_load_ignores_comments_and_blank_lines() {
+        let text = "# my circuit\n\nelectron-architect 1\nnode 7 and 1 1\n  # nothing\nnode 3 never 0 1\nwire 3 7\n";
+        let graph = Graph::load(text.as_bytes()).unwrap();
+        let and = &graph.nodes[graph.find_node_at(Vector2i::new(1, 1)).unwrap()];
+        assert_eq!(and.inputs.len(), 1);
+        assert!(and.inputs[0].elbows.is_empty());
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let load = |text: &str| Graph::load(text.as_bytes()).expect_err("load should fail");
+        assert!(matches!(load(""), LoadError::MissingHeader));
+        assert!(matches!(load("node 0 and 0 0"), LoadError::MissingHeader));
+        assert!(matches!(load("electron-architect 99"), LoadError::UnsupportedVersion(99)));
+        assert!(matches!(load("electron-architect 1\nnode 0 maybe 0 0"), LoadError::Syntax { line: 2, .. }));
+        assert!(matches!(load("electron-architect 1\nnode 0 and 0"), LoadError::Syntax { line: 2, .. }));
+        assert!(matches!(load("electron-architect 1\nnode 0 and 0 0 0"), LoadError::Syntax { line: 2, .. }));
+        assert!(matches!(load("electron-architect 1\nnode 0 and 0 0\nnode 0 or 1 1"), LoadError::DuplicateNode { line: 3, id: 0 }));
+        assert!(matches!(load("electron-architect 1\nnode 0 and 0 0\nnode 1 or 0 0"), LoadError::OverlappingNode { line: 3, .. }));
+        assert!(matches!(load("electron-architect 1\nnode 0 and 0 0\nwire 0 1"), LoadError::UnknownNode { line: 3, id: 1 }));
+        assert!(matches!(load("electron-architect 1\nnode 0 and 0 0\nwire 0 0 1;2"), LoadError::Syntax { line: 3, .. }));
+        assert!(matches!(load("electron-architect 1\nbogus"), LoadError::Syntax { line: 2, .. }));
+        assert!(matches!(load("electron-architect 1\nnode 0 and 0 0\nwire 0 0"), LoadError::InvalidWire { line: 3, error: WireError::SelfLoop }));
+        assert!(matches!(
+            load("electron-architect 1\nnode 0 and 0 0\nnode 1 not 1 0\nwire 0 1\nwire 0 1"),
+            LoadError::InvalidWire { line: 5, error: WireError::Duplicate },
+        ));
+        assert!(matches!(load("electron-architect 2\nlabel 0 a"), LoadError::UnknownNode { line: 2, id: 0 }));
+        assert!(matches!(load("electron-architect 2\nnode 0 and 0 0\nlabel 0"), LoadError::Syntax { line: 3, .. }));
+        assert!(matches!(load("electron-architect 2\nnode 0 and 0 0\nnode 1 and 1 0\nlabel 0 a\nlabel 1 a"), LoadError::DuplicateLabel { line: 5, .. }));
+    }
+}
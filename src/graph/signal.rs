@@ -0,0 +1,159 @@
+//! Values carried by wires.
+//!
+//! A signal is one or more bits, up to [`Signal::MAX_WIDTH`]. Single bits are the common case; wider signals are
+//! buses, made and taken apart by [`Gate::Join`](super::node::gate::Gate::Join) and
+//! [`Gate::Split`](super::node::gate::Gate::Split).
+//!
+//! Bitwise operations on signals of different widths give a result as wide as the wider one. The narrower side is
+//! [resized](Signal::resized) to match, so a single bit applies to every bit of a bus, like an enable line.
+
+use std::{fmt, ops::{BitAnd, BitOr, BitXor, Not}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Signal {
+    /// Only the low `width` bits are ever set
+    bits: u64,
+    width: u8,
+}
+
+impl Default for Signal {
+    fn default() -> Self {
+        Self::LOW
+    }
+}
+
+impl Signal {
+    pub const MAX_WIDTH: u8 = 64;
+
+    /// A single low bit
+    pub const LOW: Self = Self { bits: 0, width: 1 };
+    /// A single high bit
+    pub const HIGH: Self = Self { bits: 1, width: 1 };
+
+    /// The low `width` bits of `bits`, with the width clamped to between 1 and [`Signal::MAX_WIDTH`]
+    pub const fn new(bits: u64, width: u8) -> Self {
+        let width = if width < 1 { 1 } else if width > Self::MAX_WIDTH { Self::MAX_WIDTH } else { width };
+        Self { bits: bits & Self::mask(width), width }
+    }
+
+    pub const fn bit(state: bool) -> Self {
+        if state { Self::HIGH } else { Self::LOW }
+    }
+
+    /// The lowest `width` bits set
+    const fn mask(width: u8) -> u64 {
+        if width >= 64 { u64::MAX } else { (1 << width) - 1 }
+    }
+
+    pub const fn bits(self) -> u64 {
+        self.bits
+    }
+
+    pub const fn width(self) -> u8 {
+        self.width
+    }
+
+    /// Whether any bit is set; this is how a bus reads wherever a single bit is expected
+    pub const fn is_high(self) -> bool {
+        self.bits != 0
+    }
+
+    /// The `index`th bit, counting from the least significant; `false` past the last one
+    pub const fn get(self, index: usize) -> bool {
+        index < self.width as usize && self.bits >> index & 1 == 1
+    }
+
+    /// The signal at another width. Narrowing drops the high bits, and widening repeats a single bit
+    /// or pads anything wider with low bits.
+    pub const fn resized(self, width: u8) -> Self {
+        if width > self.width && self.width == 1 {
+            Self::new(if self.is_high() { u64::MAX } else { 0 }, width)
+        } else {
+            Self::new(self.bits, width)
+        }
+    }
+
+    /// Applies `op` to both signals, resized to the wider of the two
+    fn combine(self, other: Self, op: impl Fn(u64, u64) -> u64) -> Self {
+        let width = self.width.max(other.width);
+        Self::new(op(self.resized(width).bits, other.resized(width).bits), width)
+    }
+}
+
+impl Not for Signal {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::new(!self.bits, self.width)
+    }
+}
+
+impl BitAnd for Signal {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        self.combine(rhs, |a, b| a & b)
+    }
+}
+
+impl BitOr for Signal {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.combine(rhs, |a, b| a | b)
+    }
+}
+
+impl BitXor for Signal {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self {
+        self.combine(rhs, |a, b| a ^ b)
+    }
+}
+
+/// Single bits as `0` or `1`, and buses in hexadecimal with a digit for every four bits
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.width {
+            1 => write!(f, "{}", self.bits),
+            width => write!(f, "0x{:0digits$x}", self.bits, digits = (width as usize).div_ceil(4)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitwise_over_widths() {
+        let a = Signal::new(0b1100, 4);
+        let b = Signal::new(0b1010, 4);
+        assert_eq!(a & b, Signal::new(0b1000, 4));
+        assert_eq!(a | b, Signal::new(0b1110, 4));
+        assert_eq!(a ^ b, Signal::new(0b0110, 4));
+        assert_eq!(!a, Signal::new(0b0011, 4), "inverting stays within the width");
+
+        assert_eq!(a & Signal::HIGH, a, "a single bit applies to every bit of a bus");
+        assert_eq!(a ^ Signal::HIGH, !a);
+        assert_eq!(Signal::new(0b11, 2) | Signal::new(0b1000, 4), Signal::new(0b1011, 4), "narrower buses are padded");
+        assert_eq!(Signal::new(0x1ff, 8), Signal::new(0xff, 8), "bits past the width are dropped");
+        assert_eq!(!Signal::new(0, 64), Signal::new(u64::MAX, 64));
+    }
+
+    #[test]
+    fn test_bits() {
+        let signal = Signal::new(0b101, 3);
+        assert!(signal.get(0) && !signal.get(1) && signal.get(2));
+        assert!(!signal.get(3));
+        assert!(signal.is_high());
+        assert!(!Signal::new(0, 8).is_high());
+        assert_eq!(signal.resized(2), Signal::new(0b01, 2));
+        assert_eq!(Signal::new(7, 0).width(), 1, "widths are at least one bit");
+
+        assert_eq!(Signal::HIGH.to_string(), "1");
+        assert_eq!(Signal::new(0xa, 8).to_string(), "0x0a");
+        assert_eq!(Signal::new(0x1f, 5).to_string(), "0x1f");
+    }
+}
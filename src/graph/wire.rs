@@ -1,27 +1,151 @@
-use std::{cell::RefCell, fmt, rc::{Rc, Weak}};
+use std::{error::Error, fmt};
 use crate::vector2i::Vector2i;
 
-use super::node::Node;
+use super::{node::gate::Gate, NodeId};
 
-#[derive(Debug)]
+/// Reasons a wire can be rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireError {
+    /// The output node's gate already has as many inputs as it can use
+    InputLimitExceeded { gate: Gate, max: usize },
+    SelfLoop,
+    /// The same ports of the nodes are already wired in this direction
+    Duplicate,
+    /// One of the nodes is no longer part of the graph
+    DanglingNode,
+    /// The wire reads an output port past the last one its input node has
+    NoSuchOutput { outputs: usize },
+    /// The wire goes to an input port past the last one its output node has
+    NoSuchInput { inputs: usize },
+    /// Another wire already goes to the input port, which takes only one
+    PortTaken,
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InputLimitExceeded { gate, max: 0 } => write!(f, "{} gates take no inputs", gate.name()),
+            Self::InputLimitExceeded { gate, max: 1 } => write!(f, "{} gates take only 1 input", gate.name()),
+            Self::InputLimitExceeded { gate, max } => write!(f, "{} gates take at most {max} inputs", gate.name()),
+            Self::SelfLoop => write!(f, "a node cannot be wired to itself"),
+            Self::Duplicate => write!(f, "those nodes are already wired"),
+            Self::DanglingNode => write!(f, "node is no longer in the graph"),
+            Self::NoSuchOutput { outputs: 1 } => write!(f, "that node has only 1 output"),
+            Self::NoSuchOutput { outputs } => write!(f, "that node has only {outputs} outputs"),
+            Self::NoSuchInput { inputs: 1 } => write!(f, "that node has only 1 input"),
+            Self::NoSuchInput { inputs } => write!(f, "that node has only {inputs} inputs"),
+            Self::PortTaken => write!(f, "that input is already wired"),
+        }
+    }
+}
+
+impl Error for WireError {}
+
+/// Which way the L-shaped bend between two cells turns
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ElbowOrientation {
+    /// Along the row of the start, then down the column of the end
+    #[default]
+    HorizontalFirst,
+    /// Down the column of the start, then along the row of the end
+    VerticalFirst,
+}
+
+impl ElbowOrientation {
+    pub fn flipped(self) -> Self {
+        match self {
+            Self::HorizontalFirst => Self::VerticalFirst,
+            Self::VerticalFirst => Self::HorizontalFirst,
+        }
+    }
+
+    /// The corner of the L going from `from` to `to`
+    pub fn corner(self, from: Vector2i, to: Vector2i) -> Vector2i {
+        match self {
+            Self::HorizontalFirst => Vector2i::new(to.x, from.y),
+            Self::VerticalFirst => Vector2i::new(from.x, to.y),
+        }
+    }
+
+    /// Elbows for a wire from `start` through each waypoint to `end`,
+    /// with a corner wherever two consecutive points share neither a row nor a column.
+    pub fn route(self, start: Vector2i, waypoints: &[Vector2i], end: Vector2i) -> Vec<Vector2i> {
+        let mut elbows = Vec::with_capacity(waypoints.len() * 2 + 1);
+        let mut previous = start;
+        for (i, &next) in waypoints.iter().chain([&end]).enumerate() {
+            if next == previous {
+                continue;
+            }
+            if next.x != previous.x && next.y != previous.y {
+                elbows.push(self.corner(previous, next));
+            }
+            if i < waypoints.len() {
+                elbows.push(next);
+            }
+            previous = next;
+        }
+        elbows
+    }
+}
+
+/// Refers to the wire from output port `output_port` of `input` to input port `input_port` of `output`,
+/// of which there is at most one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WireHandle {
+    pub input: NodeId,
+    pub output_port: usize,
+    pub output: NodeId,
+    pub input_port: usize,
+}
+
+/// Refers to one elbow of a wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElbowHandle {
+    pub wire: WireHandle,
+    pub index: usize,
+}
+
+/// A wire into the node that holds it
+#[derive(Debug, Clone)]
 pub struct Wire {
-    pub(in crate::graph) input: Weak<RefCell<Node>>,
-    pub(in crate::graph) owner: Weak<RefCell<Node>>,
+    pub(in crate::graph) input: NodeId,
+    /// Which of the input node's output ports the wire reads
+    pub(in crate::graph) output_port: usize,
+    /// Which of the holding node's input ports the wire drives
+    pub(in crate::graph) input_port: usize,
     pub(in crate::graph) elbows: Vec<Vector2i>,
 }
 
 impl Wire {
-    pub fn new(input: &Rc<RefCell<Node>>, output: &Rc<RefCell<Node>>, elbows: Vec<Vector2i>) -> Self {
-        Self {
-            input: Rc::downgrade(input),
-            owner: Rc::downgrade(output),
-            elbows,
-        }
+    pub fn new(input: NodeId, output_port: usize, input_port: usize, elbows: Vec<Vector2i>) -> Self {
+        Self { input, output_port, input_port, elbows }
+    }
+
+    /// Refers to this wire, held by `output`
+    pub fn handle(&self, output: NodeId) -> WireHandle {
+        WireHandle { input: self.input, output_port: self.output_port, output, input_port: self.input_port }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        use ElbowOrientation::*;
+        let (start, end) = (Vector2i::new(0, 0), Vector2i::new(4, 3));
+        assert_eq!(HorizontalFirst.route(start, &[], end), [Vector2i::new(4, 0)]);
+        assert_eq!(VerticalFirst.route(start, &[], end), [Vector2i::new(0, 3)]);
+        assert_eq!(HorizontalFirst.route(start, &[], Vector2i::new(4, 0)), [], "aligned cells need no bend");
 
-    pub fn evaluate(&self) -> Option<bool> {
-        self.input
-            .upgrade()
-            .map(|input_node| input_node.borrow().evaluate())
+        let waypoints = [Vector2i::new(2, 2), Vector2i::new(2, 5)];
+        assert_eq!(HorizontalFirst.route(start, &waypoints, end), [
+            Vector2i::new(2, 0),
+            Vector2i::new(2, 2),
+            Vector2i::new(2, 5),
+            Vector2i::new(4, 5),
+        ]);
+        assert_eq!(HorizontalFirst.route(start, &[start, end], end), [Vector2i::new(4, 0), end], "repeated points add no bends");
     }
 }
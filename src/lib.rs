@@ -0,0 +1,4 @@
+#![allow(unused)]
+
+pub mod vector2i;
+pub mod graph;
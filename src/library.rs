@@ -0,0 +1,107 @@
+use std::{fs::{self, File}, io::{self, BufReader}, path::PathBuf};
+use raylib::prelude::*;
+use electron_architect_rs_2::graph::{component::Component, Graph, NodeId};
+
+/// Components saved as circuit files in a directory, each named after its file.
+/// Listed down the left edge of the window in edit mode, to be placed instead of the palette's gate.
+pub struct Library {
+    directory: PathBuf,
+    components: Vec<Component>,
+    /// Index of the component being placed, if any
+    pub selected: Option<usize>,
+}
+
+impl Library {
+    const EXTENSION: &'static str = "txt";
+    /// Just below the palette
+    const TOP: f32 = 50.0;
+    const SLOT_WIDTH: f32 = 120.0;
+    const SLOT_HEIGHT: f32 = 20.0;
+    const MARGIN: f32 = 6.0;
+    const FONT_SIZE: i32 = 10;
+
+    /// Loads every circuit in the directory, which doesn't have to exist yet.
+    /// Files that fail to load are left out and described in the returned messages.
+    pub fn load(directory: impl Into<PathBuf>) -> (Self, Vec<String>) {
+        let mut library = Self { directory: directory.into(), components: Vec::new(), selected: None };
+        let mut errors = Vec::new();
+        let Ok(entries) = fs::read_dir(&library.directory) else {
+            return (library, errors);
+        };
+        for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+            if path.extension().and_then(|extension| extension.to_str()) != Some(Self::EXTENSION) {
+                continue;
+            }
+            let loaded = File::open(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|file| Graph::load(BufReader::new(file)).map_err(|e| e.to_string()))
+                .and_then(|circuit| Component::new(name, circuit).map_err(|e| e.to_string()));
+            match loaded {
+                Ok(component) => library.components.push(component),
+                Err(e) => errors.push(format!("{}: {e}", path.display())),
+            }
+        }
+        library.components.sort_by(|a, b| a.name().cmp(b.name()));
+        (library, errors)
+    }
+
+    pub fn selected(&self) -> Option<&Component> {
+        self.selected.and_then(|index| self.components.get(index))
+    }
+
+    /// Writes the component's circuit into the directory, replacing any component with the same name, and selects it.
+    pub fn save(&mut self, component: Component) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(format!("{}.{}", component.name(), Self::EXTENSION));
+        component.circuit().save(&mut File::create(path)?)?;
+
+        self.components.retain(|existing| existing.name() != component.name());
+        let name = component.name().to_string();
+        self.components.push(component);
+        self.components.sort_by(|a, b| a.name().cmp(b.name()));
+        self.selected = self.components.iter().position(|component| component.name() == name);
+        Ok(())
+    }
+
+    /// Screen space rectangle of the slot for the `index`th component
+    fn slot(index: usize) -> Rectangle {
+        Rectangle::new(
+            Self::MARGIN,
+            Self::TOP + index as f32 * (Self::SLOT_HEIGHT + Self::MARGIN),
+            Self::SLOT_WIDTH,
+            Self::SLOT_HEIGHT,
+        )
+    }
+
+    /// Whether the screen space point is over the list
+    pub fn contains(&self, point: Vector2) -> bool {
+        self.component_at(point).is_some()
+    }
+
+    /// Index of the component whose slot contains the screen space point
+    pub fn component_at(&self, point: Vector2) -> Option<usize> {
+        (0..self.components.len()).find(|&index| Self::slot(index).check_collision_point_rec(point))
+    }
+
+    pub fn draw(&self, d: &mut impl RaylibDraw) {
+        for (index, component) in self.components.iter().enumerate() {
+            let slot = Self::slot(index);
+            d.draw_rectangle_rec(slot, Color::new(70, 190, 190, 255));
+            if self.selected == Some(index) {
+                d.draw_rectangle_lines_ex(Rectangle::new(slot.x - 3.0, slot.y - 3.0, slot.width + 6.0, slot.height + 6.0), 2.0, Color::YELLOW);
+            }
+            let text = format!("{} ({} in, {} out)", component.name(), component.input_count(), component.output_count());
+            d.draw_text(&text, slot.x as i32 + 4, (slot.y + slot.height * 0.5) as i32 - Self::FONT_SIZE / 2, Self::FONT_SIZE, Color::BLACK);
+        }
+    }
+}
+
+/// Label that marks the node as the next free pin of a component made from it:
+/// `in0`, `in1`, ... for input gates, and `out0`, `out1`, ... for the rest
+pub fn next_pin_label(graph: &Graph, id: NodeId) -> Option<String> {
+    let prefix = if graph.node(id)?.gate().is_input() { "in" } else { "out" };
+    (0..)
+        .map(|index| format!("{prefix}{index}"))
+        .find(|label| graph.find_node_by_label(label).is_none())
+}
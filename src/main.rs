@@ -1,13 +1,60 @@
 #![allow(unused)]
 
-use std::{cell::RefCell, rc::{Rc, Weak}};
+use std::{fs::File, io::BufReader};
 
-use graph::{node::{gate::Gate, Node}, quad_tree::{InfiniteQuadTree, Positioned}, Graph};
+use electron_architect_rs_2::{graph::{component::Component, history::History, node::gate::Gate, save::LoadError, wire::{ElbowHandle, WireHandle}, Graph, NodeId}, vector2i::Vector2i};
+use clock::SimulationClock;
+use library::{next_pin_label, Library};
+use palette::Palette;
+use prompt::{Prompt, PromptResult};
 use raylib::prelude::*;
-use vector2i::Vector2i;
+use selection::Selection;
+use status::StatusLine;
+use view::View;
+use wiring::WireTool;
 
-mod vector2i;
-mod graph;
+mod clock;
+mod library;
+mod palette;
+mod prompt;
+mod selection;
+mod status;
+mod view;
+mod wiring;
+
+/// How close, in pixels, the cursor has to be to a wire to pick it
+const WIRE_PICK_DISTANCE: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Clicks place, wire and remove nodes
+    Edit,
+    /// Clicks operate toggles and buttons
+    Interact,
+}
+
+/// Where saved components are kept, relative to the working directory
+const LIBRARY_DIRECTORY: &str = "components";
+
+/// What a click places: the component picked from the library, or else the palette's gate.
+/// A component saved again since it was last placed replaces the graph's copy, which fails if the nodes
+/// already placed from it no longer fit.
+fn placed_gate(graph: &mut Graph, palette: &Palette, library: &Library) -> Result<Gate, String> {
+    match library.selected() {
+        Some(component) => graph
+            .update_component(component.clone())
+            .map(Gate::Component)
+            .map_err(|e| format!("cannot update the placed copies of {}: {e}", component.name())),
+        None => Ok(palette.selected),
+    }
+}
+
+/// A node held down by the left mouse button in edit mode
+struct Drag {
+    node: NodeId,
+    /// Whether the node has left the cell it was grabbed in; a drag that never moves counts as a click
+    moved: bool,
+}
 
 fn main() {
     let window_width = 1280.0;
@@ -19,58 +66,414 @@ fn main() {
 
     rl.set_exit_key(None);
 
-    const GRID_COLOR: Color = Color::new(16, 16, 16, 255);
-
     let mut graph = Graph::new();
-    let grid_bottom_right = graph.world_to_grid(Vector2::new(
-        window_width  + Graph::GRID_SIZE,
-        window_height + Graph::GRID_SIZE,
-    ));
+    let mut history = History::new();
+
+    let mut mode = Mode::Edit;
+    let mut wire_tool = WireTool::new();
+    let mut held_button: Option<NodeId> = None;
+    let mut dragging: Option<Drag> = None;
+    let mut dragging_elbow: Option<ElbowHandle> = None;
+    let mut selected_wire: Option<WireHandle> = None;
+    let mut selection = Selection::new();
+    // Where the left mouse button went down over empty space, which becomes a box selection if dragged
+    let mut box_start: Option<Vector2> = None;
+    let mut palette = Palette::new();
+    let mut status = StatusLine::new();
+    let mut clock = SimulationClock::new();
+    let mut view = View::new();
+    let (mut library, library_errors) = Library::load(LIBRARY_DIRECTORY);
+    if let Some(e) = library_errors.first() {
+        status.error(&rl, format!("failed to load component {e}"));
+    }
+    // Open while naming a component
+    let mut prompt: Option<Prompt> = None;
 
-    let mut current_node: Option<Weak<RefCell<Node>>> = None;
+    let circuit_path = std::env::args().nth(1).unwrap_or_else(|| String::from("circuit.txt"));
 
     while !rl.window_should_close() {
+        view.handle_input(&rl);
+
         let mouse_pos = rl.get_mouse_position();
-        let mouse_cell = graph.world_to_grid(mouse_pos);
+        let mouse_world = view.screen_to_world(&rl, mouse_pos);
+        let mouse_cell = graph.world_to_grid(mouse_world);
+        let over_palette = mode == Mode::Edit && (palette.contains(mouse_pos) || library.contains(mouse_pos));
+        let hovered = if over_palette {
+            None
+        } else {
+            graph.find_node_at(mouse_cell)
+        };
+        // Nodes take priority over the wires running into them
+        let hovered_wire = if mode == Mode::Edit && !over_palette && hovered.is_none() {
+            graph.find_wire_at(mouse_world, WIRE_PICK_DISTANCE / view.camera.zoom)
+        } else {
+            None
+        };
 
-        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT) {
-            if current_node.is_some() {
-                current_node = None;
-            } else if let Some(hovered) = graph.find_node_at(mouse_cell) {
-                println!("todo: remove node from graph");
+        // Keys go to the prompt while it is open, including the one that closes it
+        let typing = prompt.is_some();
+        if let Some(open) = prompt.as_mut() {
+            match open.update(&mut rl) {
+                PromptResult::Open => (),
+                PromptResult::Cancelled => prompt = None,
+                PromptResult::Submitted(name) => {
+                    prompt = None;
+                    let saved = Component::from_nodes(&graph, name, selection.nodes(&graph))
+                        .map_err(|e| e.to_string())
+                        .and_then(|component| {
+                            let name = component.name().to_string();
+                            library.save(component).map(|()| name).map_err(|e| e.to_string())
+                        });
+                    match saved {
+                        Ok(name) => status.info(&rl, format!("saved component {name}")),
+                        Err(e) => status.error(&rl, format!("failed to save component: {e}")),
+                    }
+                },
             }
         }
 
-        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
-            let new_node = graph.put_node(Gate::Always, mouse_cell);
-            if let Some(current_node) = current_node {
-                if let (Some(input), Some(output)) = (current_node.upgrade(), new_node.upgrade()) {
-                    graph.wire(input, output);
-                }
+        let ctrl_down = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+        if !typing && ctrl_down && rl.is_key_pressed(KeyboardKey::KEY_S) {
+            match File::create(&circuit_path).and_then(|mut file| graph.save(&mut file)) {
+                Ok(()) => status.info(&rl, format!("saved to {circuit_path}")),
+                Err(e) => status.error(&rl, format!("failed to save {circuit_path}: {e}")),
+            }
+        }
+        if !typing && ctrl_down && rl.is_key_pressed(KeyboardKey::KEY_O) {
+            let loaded = File::open(&circuit_path)
+                .map_err(LoadError::from)
+                .and_then(|file| Graph::load(BufReader::new(file)));
+            match loaded {
+                Ok(loaded) => {
+                    graph = loaded;
+                    history.clear();
+                    wire_tool.cancel();
+                    dragging = None;
+                    dragging_elbow = None;
+                    selected_wire = None;
+                    selection.clear();
+                    box_start = None;
+                    status.info(&rl, format!("loaded {circuit_path}"));
+                },
+                Err(e) => status.error(&rl, format!("failed to load {circuit_path}: {e}")),
             }
-            current_node = Some(new_node);
         }
 
+        if !typing && rl.is_key_pressed(KeyboardKey::KEY_TAB) {
+            mode = match mode {
+                Mode::Edit => Mode::Interact,
+                Mode::Interact => Mode::Edit,
+            };
+            wire_tool.cancel();
+            dragging = None;
+            dragging_elbow = None;
+            selected_wire = None;
+            selection.clear();
+            box_start = None;
+            history.end_group();
+        }
+
+        if rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT) {
+            if let Some(button) = held_button.take() {
+                graph.drive(button, false);
+            }
+        }
+
+        match mode {
+            _ if typing => (),
+            Mode::Interact => if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+                if let Some(hovered) = hovered {
+                    match graph.node(hovered).map(|node| node.gate()) {
+                        Some(Gate::Toggle(on)) => {
+                            graph.drive(hovered, !on);
+                        },
+                        Some(Gate::Button(_)) => {
+                            graph.drive(hovered, true);
+                            held_button = Some(hovered);
+                        },
+                        _ => (),
+                    }
+                }
+            },
+            Mode::Edit => {
+                let shift_down = rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+                let undo = ctrl_down && !shift_down && rl.is_key_pressed(KeyboardKey::KEY_Z);
+                let redo = ctrl_down && (rl.is_key_pressed(KeyboardKey::KEY_Y) || (shift_down && rl.is_key_pressed(KeyboardKey::KEY_Z)));
+                if undo || redo {
+                    let done = if undo { history.undo(&mut graph) } else { history.redo(&mut graph) };
+                    if done {
+                        // Undoing can bring back nodes and wires under new IDs, so nothing held onto is reliable
+                        wire_tool.cancel();
+                        dragging = None;
+                        dragging_elbow = None;
+                        selected_wire = None;
+                        box_start = None;
+                    } else if (undo && history.can_undo()) || (redo && history.can_redo()) {
+                        // The group is still there; the graph just doesn't match it any more
+                        status.error(&rl, if undo { "cannot undo: the circuit has changed" } else { "cannot redo: the circuit has changed" });
+                    } else {
+                        status.info(&rl, if undo { "nothing to undo" } else { "nothing to redo" });
+                    }
+                }
+                if ctrl_down && (rl.is_key_pressed(KeyboardKey::KEY_C) || rl.is_key_pressed(KeyboardKey::KEY_X)) && !selection.is_empty(&graph) {
+                    // The clipboard holds a circuit in the save format, so snippets can be shared as plain text
+                    let nodes = selection.nodes(&graph);
+                    let mut text = Vec::new();
+                    graph.save_nodes(nodes.iter().copied(), &mut text).expect("writing to memory cannot fail");
+                    let text = String::from_utf8(text).expect("circuits are saved as UTF-8");
+                    let cut = rl.is_key_pressed(KeyboardKey::KEY_X);
+                    let verb = if cut { "cut" } else { "copy" };
+                    match rl.set_clipboard_text(&text) {
+                        Ok(()) if cut => {
+                            status.info(&rl, format!("cut {} nodes", nodes.len()));
+                            history.begin_group();
+                            for node in nodes {
+                                history.remove_node(&mut graph, node);
+                            }
+                            history.end_group();
+                            selection.clear();
+                        },
+                        Ok(()) => status.info(&rl, format!("copied {} nodes", nodes.len())),
+                        // Nothing is removed if the nodes didn't make it to the clipboard
+                        Err(e) => status.error(&rl, format!("failed to {verb}: {e}")),
+                    }
+                }
+                if ctrl_down && rl.is_key_pressed(KeyboardKey::KEY_V) {
+                    let snippet = rl.get_clipboard_text()
+                        .map_err(|e| e.to_string())
+                        .and_then(|text| Graph::load(text.as_bytes()).map_err(|e| e.to_string()));
+                    match snippet {
+                        Ok(snippet) => match history.paste(&mut graph, &snippet, mouse_cell) {
+                            Ok(pasted) => selection.set(pasted),
+                            Err(e) => status.error(&rl, format!("cannot paste here: {e}")),
+                        },
+                        Err(e) => status.error(&rl, format!("clipboard does not hold a circuit: {e}")),
+                    }
+                }
+                if let Some(gate) = palette.hotkey_pressed(&rl) {
+                    palette.selected = gate;
+                    library.selected = None;
+                }
+                if !ctrl_down && rl.is_key_pressed(KeyboardKey::KEY_G) {
+                    if let Some(hovered) = hovered {
+                        let set = placed_gate(&mut graph, &palette, &library)
+                            .and_then(|gate| history.set_gate(&mut graph, hovered, gate).map_err(|e| e.to_string()));
+                        if let Err(e) = set {
+                            status.error(&rl, e);
+                        }
+                    }
+                }
+                if ctrl_down && rl.is_key_pressed(KeyboardKey::KEY_G) && !selection.is_empty(&graph) {
+                    // The selection's labeled nodes become the component's pins
+                    prompt = Some(Prompt::new("component name:"));
+                }
+                if !ctrl_down && rl.is_key_pressed(KeyboardKey::KEY_P) {
+                    if let Some(hovered) = hovered {
+                        let label = match graph.node(hovered).and_then(|node| node.label()) {
+                            Some(_) => None,
+                            None => next_pin_label(&graph, hovered),
+                        };
+                        history.set_label(&mut graph, hovered, label.clone());
+                        match label {
+                            Some(label) => status.info(&rl, format!("marked pin {label}")),
+                            None => status.info(&rl, "unmarked pin"),
+                        }
+                    }
+                }
+                let resize = if rl.is_key_pressed(KeyboardKey::KEY_LEFT_BRACKET) {
+                    Some(-1)
+                } else if rl.is_key_pressed(KeyboardKey::KEY_RIGHT_BRACKET) {
+                    Some(1)
+                } else {
+                    None
+                };
+                if let (Some(step), Some(hovered)) = (resize, hovered) {
+                    // Brackets narrow and widen splitters and joiners
+                    let gate = graph.node(hovered).map(|node| node.gate());
+                    let resized = gate.and_then(|gate| gate.with_width(gate.width()?.saturating_add_signed(step)));
+                    if let Some(resized) = resized.filter(|&resized| Some(resized) != gate) {
+                        match history.set_gate(&mut graph, hovered, resized) {
+                            Ok(()) => status.info(&rl, format!("{} bits wide", resized.width().unwrap_or(1))),
+                            Err(e) => status.error(&rl, e.to_string()),
+                        }
+                    }
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_F) {
+                    wire_tool.flip();
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+                    wire_tool.cancel();
+                    selected_wire = None;
+                    selection.clear();
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_DELETE) || rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+                    if !selection.is_empty(&graph) {
+                        history.begin_group();
+                        for node in selection.nodes(&graph) {
+                            history.remove_node(&mut graph, node);
+                        }
+                        history.end_group();
+                        selection.clear();
+                    } else if let Some(wire) = selected_wire.take() {
+                        history.unwire(&mut graph, &wire);
+                    }
+                }
+                if rl.is_key_pressed(KeyboardKey::KEY_R) && wire_tool.is_active(&graph) {
+                    let message = if wire_tool.toggle_auto_route() { "wires route around nodes" } else { "wires bend by hand" };
+                    status.info(&rl, message);
+                } else if rl.is_key_pressed(KeyboardKey::KEY_R) {
+                    // Shift reroutes everything on screen instead of just what is selected or the hovered node's wires
+                    if !selection.is_empty(&graph) {
+                        let nodes = selection.nodes(&graph);
+                        history.reroute_wires(&mut graph, nodes);
+                    } else if let Some(wire) = selected_wire.as_ref() {
+                        history.reroute_wire(&mut graph, wire);
+                    } else if shift_down {
+                        let visible = view.visible_world(&rl);
+                        let min = graph.world_to_grid(Vector2::new(visible.x, visible.y));
+                        let max = graph.world_to_grid(Vector2::new(visible.x + visible.width, visible.y + visible.height));
+                        let nodes: Vec<_> = graph.nodes_in_rect(min, max).collect();
+                        history.reroute_wires(&mut graph, nodes);
+                    } else if let Some(hovered) = hovered {
+                        history.reroute_wires(&mut graph, [hovered]);
+                    }
+                }
+
+                if over_palette {
+                    if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+                        if let Some(gate) = palette.gate_at(mouse_pos) {
+                            palette.selected = gate;
+                            library.selected = None;
+                        } else if let Some(index) = library.component_at(mouse_pos) {
+                            library.selected = Some(index);
+                        }
+                    }
+                } else if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT) {
+                    if let Some(current) = wire_tool.start(&graph) {
+                        // Right-clicking the far end of an existing wire removes it; anywhere else takes back
+                        // the last waypoint, then cancels the wire
+                        let unwired = hovered.is_some_and(|hovered| history.remove_wire(&mut graph, current, hovered));
+                        if !unwired && !wire_tool.pop_waypoint() {
+                            wire_tool.cancel();
+                        }
+                    } else if let Some(hovered) = hovered {
+                        history.remove_node(&mut graph, hovered);
+                    } else if let Some(elbow) = graph.find_elbow_at(mouse_cell) {
+                        history.remove_elbow(&mut graph, &elbow);
+                    } else if let Some(wire) = hovered_wire {
+                        history.unwire(&mut graph, &wire);
+                    }
+                } else if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+                    // Everything done between pressing and releasing the button is undone at once
+                    history.begin_group();
+                    selected_wire = None;
+                    match hovered {
+                        Some(hovered) if shift_down => selection.toggle(hovered),
+                        // Existing nodes are wired to on release, unless they get dragged somewhere else first
+                        Some(hovered) => dragging = Some(Drag { node: hovered, moved: false }),
+                        None if shift_down && wire_tool.is_active(&graph) => wire_tool.add_waypoint(mouse_cell),
+                        None if !wire_tool.is_active(&graph) && hovered_wire.is_some() && graph.find_elbow_at(mouse_cell).is_none() => {
+                            selected_wire = hovered_wire;
+                        },
+                        None => {
+                            dragging_elbow = if wire_tool.is_active(&graph) { None } else { graph.find_elbow_at(mouse_cell) };
+                            if dragging_elbow.is_none() {
+                                // A node gets placed on release, unless the press is dragged into a box selection
+                                box_start = Some(mouse_world);
+                            }
+                        },
+                    }
+                }
+
+                if let Some(drag) = dragging.as_mut() {
+                    if let Some(node) = graph.node(drag.node) {
+                        let offset = mouse_cell - node.position();
+                        if offset != Vector2i::default() {
+                            // Dragging any selected node brings the rest of the selection along
+                            let moved = if selection.contains(drag.node) {
+                                let nodes = selection.nodes(&graph);
+                                history.move_nodes(&mut graph, &nodes, offset)
+                            } else {
+                                history.move_node(&mut graph, drag.node, mouse_cell)
+                            };
+                            drag.moved |= moved;
+                        }
+                    }
+                }
+                if let Some(elbow) = dragging_elbow.as_ref() {
+                    history.move_elbow(&mut graph, elbow, mouse_cell);
+                }
+                if rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT) {
+                    dragging_elbow = None;
+                    if let Some(start) = box_start.take() {
+                        let start_cell = graph.world_to_grid(start);
+                        if start_cell == mouse_cell {
+                            selection.clear();
+                            // Nothing is placed if the wire in progress couldn't go into it
+                            let placed = placed_gate(&mut graph, &palette, &library)
+                                .and_then(|gate| wire_tool.can_end_at_new(&graph, gate).map(|()| gate).map_err(|e| e.to_string()));
+                            match placed {
+                                Ok(gate) => {
+                                    let new_node = history.put_node(&mut graph, gate, mouse_cell);
+                                    if let Err(e) = wire_tool.connect(&mut graph, &mut history, new_node, mouse_world) {
+                                        status.error(&rl, e.to_string());
+                                    }
+                                },
+                                Err(e) => status.error(&rl, e),
+                            }
+                        } else {
+                            // Shift adds to the selection instead of replacing it
+                            if !shift_down {
+                                selection.clear();
+                            }
+                            selection.add_rect(&graph, start_cell, mouse_cell);
+                        }
+                    }
+                    if let Some(drag) = dragging.take().filter(|drag| !drag.moved) {
+                        if let Err(e) = wire_tool.connect(&mut graph, &mut history, drag.node, mouse_world) {
+                            status.error(&rl, e.to_string());
+                        }
+                    }
+                    history.end_group();
+                }
+            },
+        }
+
+        if !typing {
+            clock.handle_input(&rl, &mut graph);
+        }
+        clock.update(&mut graph, rl.get_frame_time());
+
+        let visible = view.visible_world(&rl);
         let mut d = rl.begin_drawing(&thread);
         d.clear_background(Color::BLACK);
 
-        // Draw grid
-        for n in 0..grid_bottom_right.x.max(grid_bottom_right.y) {
-            let Vector2 { x, y } = graph.grid_to_world(Vector2i::new(n, n));
-            if x <= window_width  {
-                d.draw_rectangle_rec(Rectangle::new(x - 1.0, 0.0, 2.0, window_height), GRID_COLOR);
+        {
+            let mut d = d.begin_mode2D(view.camera);
+            view.draw_grid(&mut d, &graph, visible);
+            graph.draw_wires(&mut d, visible, hovered_wire.as_ref(), selected_wire.as_ref());
+            wire_tool.draw(&mut d, &graph, mouse_world);
+            graph.draw_nodes(&mut d, visible);
+            if let (Mode::Edit, Some(hovered)) = (mode, hovered) {
+                graph.draw_port_names(&mut d, hovered);
             }
-            if y <= window_height {
-                d.draw_rectangle_rec(Rectangle::new(0.0, y - 1.0, window_width, 2.0), GRID_COLOR);
+            selection.draw(&mut d, &graph);
+            if let Some(start) = box_start.filter(|&start| graph.world_to_grid(start) != mouse_cell) {
+                Selection::draw_box(&mut d, start, mouse_world);
             }
         }
 
-        graph.draw_wires(&mut d);
-        if let Some(current_node) = current_node.as_ref().and_then(|node| node.upgrade()) {
-            let p = graph.node_world_centered(&current_node);
-            let points = [p, Vector2::new(mouse_pos.x, p.y), mouse_pos];
-            d.draw_line_strip(&points, Color::GRAY);
+        match mode {
+            Mode::Edit => {
+                palette.draw(&mut d, library.selected.is_none());
+                library.draw(&mut d);
+            },
+            Mode::Interact => d.draw_text("INTERACT (tab to edit)", 6, 6, 20, Color::YELLOW),
+        }
+        clock.draw(&mut d, &graph);
+        match prompt.as_ref() {
+            Some(prompt) => prompt.draw(&mut d),
+            None => status.draw(&mut d),
         }
-        graph.draw_proxy_nodes(&mut d);
     }
 }
@@ -0,0 +1,89 @@
+use raylib::prelude::*;
+use electron_architect_rs_2::graph::node::gate::Gate;
+
+/// Number keys for the first ten gates
+const HOTKEYS: [KeyboardKey; 10] = [
+    KeyboardKey::KEY_ONE,
+    KeyboardKey::KEY_TWO,
+    KeyboardKey::KEY_THREE,
+    KeyboardKey::KEY_FOUR,
+    KeyboardKey::KEY_FIVE,
+    KeyboardKey::KEY_SIX,
+    KeyboardKey::KEY_SEVEN,
+    KeyboardKey::KEY_EIGHT,
+    KeyboardKey::KEY_NINE,
+    KeyboardKey::KEY_ZERO,
+];
+
+/// Toolbar for choosing which gate gets placed
+pub struct Palette {
+    pub selected: Gate,
+}
+
+impl Palette {
+    const SLOT_SIZE: f32 = 32.0;
+    const MARGIN: f32 = 6.0;
+    const GLYPH_SIZE: i32 = 20;
+    const HOTKEY_SIZE: i32 = 10;
+
+    pub fn new() -> Self {
+        Self { selected: Gate::Always }
+    }
+
+    /// Screen space rectangle of the slot for the `index`th gate
+    fn slot(index: usize) -> Rectangle {
+        Rectangle::new(
+            Self::MARGIN + index as f32 * (Self::SLOT_SIZE + Self::MARGIN),
+            Self::MARGIN,
+            Self::SLOT_SIZE,
+            Self::SLOT_SIZE,
+        )
+    }
+
+    /// Whether the screen space point is over the toolbar
+    pub fn contains(&self, point: Vector2) -> bool {
+        let first = Self::slot(0);
+        let last = Self::slot(Gate::ALL.len() - 1);
+        Rectangle::new(0.0, 0.0, last.x + last.width + Self::MARGIN, first.y + first.height + Self::MARGIN)
+            .check_collision_point_rec(point)
+    }
+
+    /// The gate whose slot contains the screen space point
+    pub fn gate_at(&self, point: Vector2) -> Option<Gate> {
+        Gate::ALL
+            .iter()
+            .enumerate()
+            .find_map(|(index, gate)| Self::slot(index).check_collision_point_rec(point).then_some(*gate))
+    }
+
+    /// The gate whose number key was pressed this frame
+    pub fn hotkey_pressed(&self, rl: &RaylibHandle) -> Option<Gate> {
+        HOTKEYS
+            .iter()
+            .zip(Gate::ALL)
+            .find_map(|(key, gate)| rl.is_key_pressed(*key).then_some(gate))
+    }
+
+    /// The selected gate is only outlined while `active`, since a component from the library can be placed instead.
+    pub fn draw(&self, d: &mut impl RaylibDraw, active: bool) {
+        for (index, gate) in Gate::ALL.iter().enumerate() {
+            let slot = Self::slot(index);
+            d.draw_rectangle_rec(slot, gate.color());
+            if active && *gate == self.selected {
+                d.draw_rectangle_lines_ex(Rectangle::new(slot.x - 3.0, slot.y - 3.0, slot.width + 6.0, slot.height + 6.0), 2.0, Color::YELLOW);
+            }
+            let glyph = gate.glyph();
+            let glyph_width = Self::GLYPH_SIZE / 2 * glyph.len() as i32;
+            d.draw_text(
+                glyph,
+                (slot.x + slot.width * 0.5) as i32 - glyph_width / 2,
+                (slot.y + slot.height * 0.5) as i32 - Self::GLYPH_SIZE / 2,
+                Self::GLYPH_SIZE,
+                Color::BLACK,
+            );
+            if index < HOTKEYS.len() {
+                d.draw_text(&((index + 1) % 10).to_string(), slot.x as i32 + 2, slot.y as i32 + 1, Self::HOTKEY_SIZE, Color::BLACK);
+            }
+        }
+    }
+}
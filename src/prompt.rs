@@ -0,0 +1,47 @@
+use raylib::prelude::*;
+
+/// What became of a prompt this frame
+pub enum PromptResult {
+    Open,
+    Submitted(String),
+    Cancelled,
+}
+
+/// Line of text typed in along the bottom of the window. Keys go to the prompt instead of the editor while it is open.
+pub struct Prompt {
+    question: String,
+    text: String,
+}
+
+impl Prompt {
+    const FONT_SIZE: i32 = 20;
+    const MARGIN: i32 = 6;
+
+    pub fn new(question: impl Into<String>) -> Self {
+        Self { question: question.into(), text: String::new() }
+    }
+
+    /// Takes this frame's typing. Enter submits the text and Escape cancels.
+    pub fn update(&mut self, rl: &mut RaylibHandle) -> PromptResult {
+        while let Some(c) = rl.get_char_pressed() {
+            if !c.is_control() {
+                self.text.push(c);
+            }
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            self.text.pop();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) || rl.is_key_pressed(KeyboardKey::KEY_KP_ENTER) {
+            return PromptResult::Submitted(std::mem::take(&mut self.text));
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+            return PromptResult::Cancelled;
+        }
+        PromptResult::Open
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        let y = d.get_screen_height() - Self::FONT_SIZE - Self::MARGIN;
+        d.draw_text(&format!("{} {}_", self.question, self.text), Self::MARGIN, y, Self::FONT_SIZE, Color::WHITE);
+    }
+}
@@ -0,0 +1,73 @@
+use raylib::prelude::*;
+use electron_architect_rs_2::{graph::{Graph, NodeId}, vector2i::Vector2i};
+
+/// Nodes picked out in edit mode to be moved or deleted together
+pub struct Selection {
+    nodes: Vec<NodeId>,
+}
+
+impl Selection {
+    const OUTLINE_COLOR: Color = Color::YELLOW;
+
+    pub fn new() -> Self {
+        Self { nodes: Vec::new() }
+    }
+
+    /// The selected nodes that are still in the graph
+    pub fn nodes(&self, graph: &Graph) -> Vec<NodeId> {
+        self.nodes.iter().copied().filter(|&id| graph.contains(id)).collect()
+    }
+
+    pub fn is_empty(&self, graph: &Graph) -> bool {
+        !self.nodes.iter().any(|&id| graph.contains(id))
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.nodes.contains(&id)
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// Selects exactly these nodes
+    pub fn set(&mut self, nodes: Vec<NodeId>) {
+        self.nodes = nodes;
+    }
+
+    /// Adds the node if it isn't selected, removes it if it is
+    pub fn toggle(&mut self, id: NodeId) {
+        match self.nodes.iter().position(|&selected| selected == id) {
+            Some(index) => {
+                self.nodes.swap_remove(index);
+            },
+            None => self.nodes.push(id),
+        }
+    }
+
+    /// Selects every node between the two corner cells, in addition to what was already selected
+    pub fn add_rect(&mut self, graph: &Graph, a: Vector2i, b: Vector2i) {
+        let min = Vector2i::new(a.x.min(b.x), a.y.min(b.y));
+        let max = Vector2i::new(a.x.max(b.x), a.y.max(b.y));
+        for id in graph.nodes_in_rect(min, max) {
+            if !self.contains(id) {
+                self.nodes.push(id);
+            }
+        }
+    }
+
+    /// Outlines the selected nodes
+    pub fn draw(&self, d: &mut impl RaylibDraw, graph: &Graph) {
+        for node in self.nodes.iter().filter_map(|&id| graph.node(id)) {
+            let Vector2 { x, y } = graph.grid_to_world(node.position());
+            d.draw_rectangle_lines_ex(Rectangle::new(x - 2.0, y - 2.0, Graph::GRID_SIZE + 4.0, Graph::GRID_SIZE + 4.0), 2.0, Self::OUTLINE_COLOR);
+        }
+    }
+
+    /// Draws the box being dragged out between two world space points
+    pub fn draw_box(d: &mut impl RaylibDraw, a: Vector2, b: Vector2) {
+        let rec = Rectangle::new(a.x.min(b.x), a.y.min(b.y), (a.x - b.x).abs(), (a.y - b.y).abs());
+        d.draw_rectangle_rec(rec, Self::OUTLINE_COLOR.fade(0.15));
+        d.draw_rectangle_lines_ex(rec, 1.0, Self::OUTLINE_COLOR);
+    }
+}
@@ -0,0 +1,42 @@
+use raylib::prelude::*;
+
+/// Message shown along the bottom of the window for a few seconds
+pub struct StatusLine {
+    message: String,
+    is_error: bool,
+    expires_at: f64,
+}
+
+impl StatusLine {
+    const DURATION: f64 = 3.0;
+    const FONT_SIZE: i32 = 20;
+    const MARGIN: i32 = 6;
+
+    pub fn new() -> Self {
+        Self {
+            message: String::new(),
+            is_error: false,
+            expires_at: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn info(&mut self, rl: &RaylibHandle, message: impl Into<String>) {
+        self.message = message.into();
+        self.is_error = false;
+        self.expires_at = rl.get_time() + Self::DURATION;
+    }
+
+    pub fn error(&mut self, rl: &RaylibHandle, message: impl Into<String>) {
+        self.info(rl, message);
+        self.is_error = true;
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        if d.get_time() >= self.expires_at {
+            return;
+        }
+        let color = if self.is_error { Color::SALMON } else { Color::LIGHTGRAY };
+        let y = d.get_screen_height() - Self::FONT_SIZE - Self::MARGIN;
+        d.draw_text(&self.message, Self::MARGIN, y, Self::FONT_SIZE, color);
+    }
+}
@@ -2,18 +2,12 @@ use std::ops::*;
 
 use raylib::prelude::Vector2;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Vector2i {
     pub x: i32,
     pub y: i32,
 }
 
-impl Default for Vector2i {
-    fn default() -> Self {
-        Self { x: Default::default(), y: Default::default() }
-    }
-}
-
 impl Vector2i {
     pub const fn new(x: i32, y: i32) -> Self {
         Self { x, y }
@@ -39,3 +33,11 @@ impl Add for Vector2i {
         Self::new(self.x + rhs.x, self.y + rhs.y)
     }
 }
+
+impl Sub for Vector2i {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
@@ -0,0 +1,74 @@
+use raylib::prelude::*;
+use electron_architect_rs_2::graph::Graph;
+
+/// Camera over the infinite canvas
+pub struct View {
+    pub camera: Camera2D,
+}
+
+impl View {
+    const MIN_ZOOM: f32 = 0.125;
+    const MAX_ZOOM: f32 = 8.0;
+    /// Zoom factor per notch of the scroll wheel
+    const ZOOM_STEP: f32 = 1.125;
+    /// Grid lines closer together than this many pixels are not drawn
+    const MIN_GRID_SPACING: f32 = 6.0;
+    const GRID_COLOR: Color = Color::new(16, 16, 16, 255);
+
+    pub fn new() -> Self {
+        Self {
+            camera: Camera2D {
+                offset: Vector2::zero(),
+                target: Vector2::zero(),
+                rotation: 0.0,
+                zoom: 1.0,
+            },
+        }
+    }
+
+    /// Middle-drag pans, the scroll wheel zooms around the cursor
+    pub fn handle_input(&mut self, rl: &RaylibHandle) {
+        if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_MIDDLE) {
+            self.camera.target -= rl.get_mouse_delta() / self.camera.zoom;
+        }
+
+        let wheel = rl.get_mouse_wheel_move();
+        if wheel != 0.0 {
+            // Anchor the camera at the cursor so the point under it stays put
+            let mouse_pos = rl.get_mouse_position();
+            self.camera.target = self.screen_to_world(rl, mouse_pos);
+            self.camera.offset = mouse_pos;
+            self.camera.zoom = (self.camera.zoom * Self::ZOOM_STEP.powf(wheel)).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        }
+    }
+
+    pub fn screen_to_world(&self, rl: &RaylibHandle, point: Vector2) -> Vector2 {
+        rl.get_screen_to_world2D(point, self.camera)
+    }
+
+    /// World space rectangle covered by the window
+    pub fn visible_world(&self, rl: &RaylibHandle) -> Rectangle {
+        let top_left = self.screen_to_world(rl, Vector2::zero());
+        let bottom_right = self.screen_to_world(rl, Vector2::new(rl.get_screen_width() as f32, rl.get_screen_height() as f32));
+        Rectangle::new(top_left.x, top_left.y, bottom_right.x - top_left.x, bottom_right.y - top_left.y)
+    }
+
+    /// Draws the grid lines over `visible`, which should come from [`View::visible_world`]
+    pub fn draw_grid(&self, d: &mut impl RaylibDraw, graph: &Graph, visible: Rectangle) {
+        if Graph::GRID_SIZE * self.camera.zoom < Self::MIN_GRID_SPACING {
+            return;
+        }
+        // Keep lines two pixels wide at any zoom
+        let thickness = 2.0 / self.camera.zoom;
+        let top_left = graph.world_to_grid(Vector2::new(visible.x, visible.y));
+        let bottom_right = graph.world_to_grid(Vector2::new(visible.x + visible.width, visible.y + visible.height));
+        for x in top_left.x..=bottom_right.x + 1 {
+            let x = x as f32 * Graph::GRID_SIZE;
+            d.draw_rectangle_rec(Rectangle::new(x - thickness * 0.5, visible.y, thickness, visible.height), Self::GRID_COLOR);
+        }
+        for y in top_left.y..=bottom_right.y + 1 {
+            let y = y as f32 * Graph::GRID_SIZE;
+            d.draw_rectangle_rec(Rectangle::new(visible.x, y - thickness * 0.5, visible.width, thickness), Self::GRID_COLOR);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use raylib::prelude::*;
+use electron_architect_rs_2::{graph::{history::History, node::gate::Gate, wire::{ElbowOrientation, WireError}, Graph, NodeId}, vector2i::Vector2i};
+
+/// The wire being drawn out of a node in edit mode
+pub struct WireTool {
+    start: Option<NodeId>,
+    /// Which output port of the start node the wire reads
+    output_port: usize,
+    /// Cells the wire has been routed through so far
+    waypoints: Vec<Vector2i>,
+    pub orientation: ElbowOrientation,
+    /// Whether bends are found by routing around nodes rather than placed by `orientation`
+    pub auto_route: bool,
+}
+
+impl WireTool {
+    pub fn new() -> Self {
+        Self {
+            start: None,
+            output_port: 0,
+            waypoints: Vec::new(),
+            orientation: ElbowOrientation::default(),
+            auto_route: false,
+        }
+    }
+
+    /// The node the wire in progress comes out of
+    pub fn start(&self, graph: &Graph) -> Option<NodeId> {
+        self.start.filter(|&id| graph.contains(id))
+    }
+
+    pub fn is_active(&self, graph: &Graph) -> bool {
+        self.start(graph).is_some()
+    }
+
+    pub fn cancel(&mut self) {
+        self.start = None;
+        self.output_port = 0;
+        self.waypoints.clear();
+    }
+
+    /// Routes the wire in progress through the cell
+    pub fn add_waypoint(&mut self, position: Vector2i) {
+        self.waypoints.push(position);
+    }
+
+    /// Undoes the most recent waypoint. Returns `false` if there were none.
+    pub fn pop_waypoint(&mut self) -> bool {
+        self.waypoints.pop().is_some()
+    }
+
+    /// Swaps which way the bends of the wire in progress turn
+    pub fn flip(&mut self) {
+        self.orientation = self.orientation.flipped();
+    }
+
+    /// Switches between routing around nodes and bending the way `orientation` says. Returns whether routing is now on.
+    pub fn toggle_auto_route(&mut self) -> bool {
+        self.auto_route = !self.auto_route;
+        self.auto_route
+    }
+
+    /// Elbows of the wire in progress if it ran from `start` through the waypoints to `end`
+    fn elbows(&self, graph: &Graph, start: Vector2i, end: Vector2i) -> Vec<Vector2i> {
+        if !self.auto_route {
+            return self.orientation.route(start, &self.waypoints, end);
+        }
+        let mut elbows = Vec::new();
+        let mut previous = start;
+        for (i, &next) in self.waypoints.iter().chain([&end]).enumerate() {
+            elbows.extend(graph.route_between(previous, next));
+            if i < self.waypoints.len() {
+                elbows.push(next);
+            }
+            previous = next;
+        }
+        elbows
+    }
+
+    /// Checks whether a new node with the gate could take the wire in progress, so it isn't placed only to be left unwired
+    pub fn can_end_at_new(&self, graph: &Graph, gate: Gate) -> Result<(), WireError> {
+        let max = graph.max_inputs(gate);
+        if self.is_active(graph) && max == 0 {
+            return Err(WireError::InputLimitExceeded { gate, max });
+        }
+        Ok(())
+    }
+
+    /// Connects the wire in progress, if any, to `node`, which then starts the next wire.
+    /// Both wires use the ports of `node` closest to `point`, the world space point that was clicked,
+    /// out of the input ports that can take another wire.
+    /// On failure the wire in progress is kept so it can be connected somewhere else.
+    pub fn connect(&mut self, graph: &mut Graph, history: &mut History, node: NodeId, point: Vector2) -> Result<(), WireError> {
+        if let (Some(input), Some(output)) = (self.start(graph), graph.node(node)) {
+            let elbows = self.elbows(graph, graph.node(input).unwrap().position(), output.position());
+            let input_port = graph.free_input_port_near(node, point).unwrap_or(0);
+            history.wire_ports_along(graph, input, self.output_port, node, input_port, elbows)?;
+        }
+        self.start = Some(node);
+        self.output_port = graph.output_port_near(node, point).unwrap_or(0);
+        self.waypoints.clear();
+        Ok(())
+    }
+
+    /// Previews the wire in progress, ending at the cursor
+    pub fn draw(&self, d: &mut impl RaylibDraw, graph: &Graph, mouse_world: Vector2) {
+        let Some(start) = self.start.and_then(|id| graph.node(id)) else { return };
+        let start_position = start.position();
+        let end_position = graph.world_to_grid(mouse_world);
+        let mut points = Vec::with_capacity(self.waypoints.len() * 2 + 3);
+        points.push(graph.output_port_position(start, self.output_port));
+        points.extend(
+            self.elbows(graph, start_position, end_position)
+                .into_iter()
+                .map(|elbow| graph.grid_to_world_centered(elbow))
+        );
+        points.push(mouse_world);
+        d.draw_line_strip(&points, Color::GRAY);
+
+        // Wires out of nodes with several outputs are labeled with the one they read
+        if graph.output_count(start.gate()) > 1 {
+            if let Some(name) = graph.output_port_name(start.gate(), self.output_port) {
+                d.draw_text(name, mouse_world.x as i32 + 8, mouse_world.y as i32 - 12, 10, Color::LIGHTGRAY);
+            }
+        }
+    }
+}